@@ -1,4 +1,11 @@
-use std::fmt::{Display, Formatter};
+use std::fmt::{Display, Formatter, Write};
+use std::num::NonZeroU32;
+use std::str::FromStr;
+
+use cozy_chess::*;
+
+use crate::nnue::{Nnue, OUTPUT_SCALE};
+use crate::search::static_exchange_evaluation;
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Eval(i16);
@@ -23,10 +30,10 @@ impl Display for EvalKind {
                 if cp < 0 {
                     write!(f, "-")?;
                 }
-                write!(f, "{}.{}", cp.abs() / 100, cp.abs() % 100)
+                write!(f, "{}.{:02}", cp.abs() / 100, cp.abs() % 100)
             },
-            EvalKind::MateIn(m) => write!(f, "M{}", (m + 1) / 2),
-            EvalKind::MatedIn(m) => write!(f, "-M{}", (m + 1) / 2)
+            EvalKind::MateIn(m) => write!(f, "M{}", m.div_ceil(2)),
+            EvalKind::MatedIn(m) => write!(f, "-M{}", m.div_ceil(2))
         }
     }
 }
@@ -44,6 +51,10 @@ impl Eval {
 
     pub const UNIT: Self = Self(1);
 
+    ///The centipawn scale of [`Eval::win_probability`]. The training data maps
+    ///win rates to centipawns with this scale, so it is the network's output scale.
+    pub const WIN_PROBABILITY_SCALE: f64 = OUTPUT_SCALE as f64;
+
     const MATE_IN_ZERO: Self = Self(i16::MAX - 100);
 
     const MAX_MATE_IN: Self = Self::mate_in(u8::MAX);
@@ -81,6 +92,433 @@ impl Eval {
             None
         }
     }
+
+    ///The probability of winning, counting a draw as half a win,
+    ///as `1 / (1 + e^(-cp / WIN_PROBABILITY_SCALE))`. Mate scores give exactly `1.0` or `0.0`.
+    ///This is the sigmoid of [`crate::tune::fit_k`] with `K = 1 / WIN_PROBABILITY_SCALE`.
+    pub fn win_probability(self) -> f64 {
+        match self.kind() {
+            EvalKind::Centipawn(cp) => sigmoid(cp as f64 / Self::WIN_PROBABILITY_SCALE),
+            EvalKind::MateIn(_) => 1.0,
+            EvalKind::MatedIn(_) => 0.0
+        }
+    }
+
+    ///The inverse of [`Eval::win_probability`] for a `probability` in `0.0..=1.0`.
+    ///The result is always a centipawn score, so `0.0` and `1.0` give the largest ones.
+    pub fn from_win_probability(probability: f64) -> Self {
+        let max = (Self::MAX_MATE_IN.0 - 1) as f64;
+        let cp = Self::WIN_PROBABILITY_SCALE * (probability / (1.0 - probability)).ln();
+        Self(cp.round().clamp(-max, max) as i16)
+    }
+
+    ///Scale a centipawn eval linearly toward a draw as the halfmove clock approaches `limit`.
+    ///Mate scores are returned unchanged.
+    pub const fn scale_by_halfmove_clock(self, halfmove_clock: u8, limit: u8) -> Self {
+        if let EvalKind::Centipawn(cp) = self.kind() {
+            if limit == 0 {
+                return Self::DRAW;
+            }
+            let clock = if halfmove_clock < limit { halfmove_clock } else { limit };
+            Self((cp as i32 * (limit - clock) as i32 / limit as i32) as i16)
+        } else {
+            self
+        }
+    }
+}
+
+///The logistic function `1 / (1 + e^(-x))`, which maps evals to win probabilities.
+pub(crate) fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+pub(crate) const DARK_SQUARES: BitBoard = bitboard! {
+    . X . X . X . X
+    X . X . X . X .
+    . X . X . X . X
+    X . X . X . X .
+    . X . X . X . X
+    X . X . X . X .
+    . X . X . X . X
+    X . X . X . X .
+};
+
+///Whether neither side can possibly checkmate. This is the case for KvK, KNvK,
+///and positions where the only other pieces are bishops all on the same color squares.
+pub fn is_insufficient_material(board: &Board) -> bool {
+    let pieces = board.occupied() ^ board.pieces(Piece::King);
+    let bishops = board.pieces(Piece::Bishop);
+    match pieces.popcnt() {
+        0 => true,
+        1 => pieces == bishops | board.pieces(Piece::Knight),
+        _ => pieces == bishops && ((bishops & DARK_SQUARES) == bishops || (bishops & DARK_SQUARES).is_empty())
+    }
+}
+
+const ROOK_ENDGAME_DRAW_SCALE: f32 = 0.25;
+
+///Scale factor for rook endgames that are usually drawn despite the material imbalance.
+///This is deliberately conservative, and only recognizes KRPvKR
+///with the defending king on the pawn's file in front of the pawn.
+pub fn rook_endgame_scale(board: &Board) -> f32 {
+    let pawns = board.pieces(Piece::Pawn);
+    let rooks = board.pieces(Piece::Rook);
+    let is_krp_vs_kr = board.occupied().popcnt() == 5
+        && pawns.popcnt() == 1
+        && rooks.popcnt() == 2
+        && (rooks & board.colors(Color::White)).popcnt() == 1;
+    if !is_krp_vs_kr {
+        return 1.0;
+    }
+    let pawn = pawns.next_square().unwrap();
+    let attacker = board.color_on(pawn).unwrap();
+    let defending_king = board.king(!attacker);
+    if defending_king.file() == pawn.file()
+        && defending_king.rank().relative_to(attacker) > pawn.rank().relative_to(attacker) {
+        ROOK_ENDGAME_DRAW_SCALE
+    } else {
+        1.0
+    }
+}
+
+const ROOK_VS_MINOR_DRAW_SCALE: f32 = 0.25;
+
+///Scale factor for KRvKN and KRvKB without pawns, which are usually drawn.
+///Any other material, even one extra pawn, is left alone.
+pub fn rook_vs_minor_scale(board: &Board) -> f32 {
+    let rooks = board.pieces(Piece::Rook);
+    let minors = board.pieces(Piece::Knight) | board.pieces(Piece::Bishop);
+    let is_kr_vs_km = board.occupied().popcnt() == 4
+        && rooks.popcnt() == 1
+        && minors.popcnt() == 1
+        && ((rooks | minors) & board.colors(Color::White)).popcnt() == 1;
+    if is_kr_vs_km {
+        ROOK_VS_MINOR_DRAW_SCALE
+    } else {
+        1.0
+    }
+}
+
+const KNOWN_DRAW_SCALE: f32 = 0.125;
+
+///Scale factor for positions where one side only has a king and the other side
+///can't force a win from a well known pattern. This only recognizes KNNvK, and
+///KBPvK with every pawn on a rook file whose promotion square the bishop can't
+///cover, with the defending king on or next to the promotion square.
+pub fn bare_king_scale(board: &Board) -> f32 {
+    let attacker = if board.colors(Color::Black).popcnt() == 1 {
+        Color::White
+    } else if board.colors(Color::White).popcnt() == 1 {
+        Color::Black
+    } else {
+        return 1.0;
+    };
+    let pieces = board.colors(attacker) ^ board.king(attacker).bitboard();
+    let knights = board.pieces(Piece::Knight);
+    let bishops = board.pieces(Piece::Bishop);
+    let pawns = board.pieces(Piece::Pawn);
+    if pieces == knights && knights.popcnt() == 2 {
+        return KNOWN_DRAW_SCALE;
+    }
+    if pieces != bishops | pawns || bishops.popcnt() != 1 || pawns.is_empty() {
+        return 1.0;
+    }
+    let file = if pawns & File::A.bitboard() == pawns {
+        File::A
+    } else if pawns & File::H.bitboard() == pawns {
+        File::H
+    } else {
+        return 1.0;
+    };
+    let promotion = Square::new(file, Rank::Eighth.relative_to(attacker));
+    let dark_bishop = !(bishops & DARK_SQUARES).is_empty();
+    let wrong_bishop = DARK_SQUARES.has(promotion) != dark_bishop;
+    let corner = get_king_moves(promotion) | promotion.bitboard();
+    if wrong_bishop && corner.has(board.king(!attacker)) {
+        KNOWN_DRAW_SCALE
+    } else {
+        1.0
+    }
+}
+
+///Scale factor for endgames that are usually drawn despite the material imbalance.
+///This is the product of [`rook_endgame_scale`], [`rook_vs_minor_scale`] and [`bare_king_scale`].
+pub fn endgame_scale(board: &Board) -> f32 {
+    rook_endgame_scale(board) * rook_vs_minor_scale(board) * bare_king_scale(board)
+}
+
+///How the static evaluation discounts likely drawn positions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawScaling {
+    ///The halfmove clock at which the eval has been scaled linearly down to zero.
+    ///`None` disables scaling by the halfmove clock.
+    pub halfmove_limit: Option<u8>,
+    ///The scale for positions where neither side can checkmate.
+    pub insufficient_material_scale: f32,
+    ///Whether to apply [`endgame_scale`].
+    pub endgame: bool
+}
+
+impl DrawScaling {
+    ///No scaling at all.
+    pub const NONE: Self = Self {
+        halfmove_limit: None,
+        insufficient_material_scale: 1.0,
+        endgame: false
+    };
+
+    ///The scaling used by [`evaluate`] and search.
+    pub const STANDARD: Self = Self {
+        halfmove_limit: Some(100),
+        insufficient_material_scale: 0.0,
+        endgame: true
+    };
+}
+
+impl Default for DrawScaling {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+///Statically evaluate `board` from scratch with the embedded network,
+///from the side to move's perspective. This matches the static evaluation used in search.
+///Neither this nor [`Nnue::state_from_board`] and [`NnueState::evaluate`](crate::nnue::NnueState::evaluate)
+///allocate.
+pub fn evaluate(board: &Board) -> Eval {
+    evaluate_scaled(board, &DrawScaling::STANDARD)
+}
+
+///Like [`evaluate`], but with custom scaling of drawish positions.
+pub fn evaluate_scaled(board: &Board, scaling: &DrawScaling) -> Eval {
+    let eval = Nnue::DEFAULT.state_from_board(board).evaluate(board.side_to_move());
+    adjust_nnue_eval(board, eval, scaling)
+}
+
+///Parse `fen` and [`evaluate`] it.
+pub fn evaluate_fen(fen: &str) -> Result<Eval, <Board as FromStr>::Err> {
+    Ok(evaluate(&fen.parse()?))
+}
+
+///Like [`evaluate`], but scores checkmate, stalemate, and the 50 move rule exactly.
+///This generates legal moves on every call, which is why [`evaluate`] doesn't.
+///Search already handles these before it evaluates a position.
+pub fn evaluate_with_status(board: &Board) -> Eval {
+    match board.status() {
+        GameStatus::Won => Eval::mated_in(0),
+        GameStatus::Drawn => Eval::DRAW,
+        GameStatus::Ongoing => evaluate(board)
+    }
+}
+
+///Flip `board` vertically and swap the colors of the pieces, castling rights, and side to move.
+///This is the same position from the other side's point of view,
+///so [`evaluate`] should give both positions exactly the same score.
+pub fn mirror_board(board: &Board) -> Board {
+    let mut builder = BoardBuilder::empty();
+    for &color in &Color::ALL {
+        let colors = board.colors(color);
+        for &piece in &Piece::ALL {
+            for square in colors & board.pieces(piece) {
+                *builder.square_mut(square.flip_rank()) = Some((piece, !color));
+            }
+        }
+        *builder.castle_rights_mut(!color) = *board.castle_rights(color);
+    }
+    builder.side_to_move = !board.side_to_move();
+    let en_passant_rank = Rank::Third.relative_to(board.side_to_move());
+    builder.en_passant = board.en_passant().map(|file| Square::new(file, en_passant_rank));
+    builder.halfmove_clock = board.halfmove_clock();
+    builder.fullmove_number = board.fullmove_number().try_into().unwrap();
+    builder.build().expect("the mirror of a valid board is valid")
+}
+
+///Whether the static evaluation of `board` can be trusted. This is false if the side
+///to move is in check, or has a capture or promotion that wins material by static
+///exchange evaluation. Only captures and promotions are generated, after the check test.
+pub fn is_quiet(board: &Board) -> bool {
+    if !board.checkers().is_empty() {
+        return false;
+    }
+    let color = board.side_to_move();
+    let their_pieces = board.colors(!color);
+    let promotion_rank = Rank::Eighth.relative_to(color).bitboard();
+    let mut quiet = true;
+    board.generate_moves(|mut moves| {
+        moves.to &= if moves.piece == Piece::Pawn {
+            their_pieces | promotion_rank
+        } else {
+            their_pieces
+        };
+        quiet = moves.into_iter().all(|mv| static_exchange_evaluation(board, mv) <= Eval::ZERO);
+        !quiet
+    });
+    quiet
+}
+
+///The net material gain of `mv` in centipawns, assuming both sides keep recapturing
+///on its target square with their least valuable piece while it gains material.
+///`mv` may also be a quiet move or promotion. En passant captures are not handled.
+pub fn see(board: &Board, mv: Move) -> i32 {
+    // Piece values are far from the mate range, so this is always a centipawn score.
+    static_exchange_evaluation(board, mv).as_cp().unwrap() as i32
+}
+
+///Like [`evaluate`], but scores a repetition of an earlier position as a draw.
+///A single repetition is enough, as in search, rather than the threefold repetition the rules require.
+///`history` holds the hashes of the earlier positions in the game, oldest first.
+///Only the last `halfmove_clock` entries are scanned, since no position before the
+///last irreversible move can repeat, so this is `O(halfmove_clock)`.
+pub fn evaluate_with_history(board: &Board, history: &[u64]) -> Eval {
+    let repeated = history.iter()
+        .rev()
+        .take(board.halfmove_clock() as usize)
+        .any(|&hash| hash == board.hash());
+    if repeated {
+        Eval::DRAW
+    } else {
+        evaluate(board)
+    }
+}
+
+///[`evaluate`] with `contempt` added from the side to move's perspective.
+pub fn evaluate_with_contempt(board: &Board, contempt: i16) -> Eval {
+    apply_contempt(board, evaluate(board), contempt)
+}
+
+///Add `contempt` to `eval`, unless `board` is a dead draw by insufficient material.
+pub(crate) fn apply_contempt(board: &Board, eval: Eval, contempt: i16) -> Eval {
+    if is_insufficient_material(board) {
+        eval
+    } else {
+        eval.saturating_add(Eval::cp(contempt))
+    }
+}
+
+// CITE: Evaluation hash table.
+// https://www.chessprogramming.org/Evaluation_Hash_Table
+///A fixed size cache of static evaluations, keyed by the full position hash.
+///A new entry always replaces the entry in its slot.
+#[derive(Debug, Clone)]
+pub struct EvalCache {
+    table: Box<[Option<(u64, Eval)>]>,
+    probes: u64,
+    hits: u64
+}
+
+impl EvalCache {
+    pub fn new_with_entries(entries: NonZeroU32) -> Self {
+        Self {
+            table: vec![None; entries.get() as usize].into_boxed_slice(),
+            probes: 0,
+            hits: 0
+        }
+    }
+
+    fn hash_to_index(&self, hash: u64) -> usize {
+        // Same reduction as the transposition table.
+        ((hash as u32 as u64 * self.table.len() as u64) >> u32::BITS) as usize
+    }
+
+    ///How many lookups have been made since the cache was created or cleared.
+    pub fn probes(&self) -> u64 {
+        self.probes
+    }
+
+    ///How many lookups found their position since the cache was created or cleared.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn clear(&mut self) {
+        self.table.fill(None);
+        self.probes = 0;
+        self.hits = 0;
+    }
+}
+
+///Like [`evaluate`], but reuses evaluations of transpositions stored in `cache`.
+///The halfmove clock isn't part of the hash, so the cache stores the evaluation
+///before scaling by the halfmove clock and scales it after the lookup.
+pub fn evaluate_cached(board: &Board, cache: &mut EvalCache) -> Eval {
+    let hash = board.hash();
+    let index = cache.hash_to_index(hash);
+    cache.probes += 1;
+    let eval = match cache.table[index] {
+        Some((entry_hash, eval)) if entry_hash == hash => {
+            cache.hits += 1;
+            eval
+        }
+        _ => {
+            let eval = evaluate_scaled(board, &DrawScaling {
+                halfmove_limit: None,
+                ..DrawScaling::STANDARD
+            });
+            cache.table[index] = Some((hash, eval));
+            eval
+        }
+    };
+    match DrawScaling::STANDARD.halfmove_limit {
+        Some(limit) => eval.scale_by_halfmove_clock(board.halfmove_clock(), limit),
+        None => eval
+    }
+}
+
+///[`evaluate`] each board, splitting the boards between the available threads.
+pub fn evaluate_batch(boards: &[Board]) -> Vec<Eval> {
+    Nnue::DEFAULT.evaluate_batch(boards)
+        .into_iter()
+        .zip(boards)
+        .map(|(eval, board)| adjust_nnue_eval(board, eval, &DrawScaling::STANDARD))
+        .collect()
+}
+
+///Describe how `board` is evaluated, for debugging and bug reports.
+///This lists the raw and centipawn network output, how many feature transformer
+///outputs clip for each perspective, the draw scaling that applies, and the final [`evaluate`] score.
+pub fn debug_position(board: &Board) -> String {
+    let state = Nnue::DEFAULT.state_from_board(board);
+    let side_to_move = board.side_to_move();
+    let mut out = String::new();
+    // Writing to a String can't fail.
+    writeln!(out, "FEN: {}", board).unwrap();
+    writeln!(out, "Side to move: {:?}", side_to_move).unwrap();
+    writeln!(
+        out,
+        "NNUE: {} cp ({} raw)",
+        state.evaluate_cp(side_to_move),
+        state.evaluate_raw(side_to_move)
+    ).unwrap();
+    for (&perspective, histogram) in Color::ALL.iter().zip(state.activation_histogram()) {
+        writeln!(
+            out,
+            "{:?} activations: {} zero, {} linear, {} saturated",
+            perspective, histogram.zero, histogram.linear, histogram.saturated
+        ).unwrap();
+    }
+    writeln!(out, "Insufficient material: {}", is_insufficient_material(board)).unwrap();
+    writeln!(out, "Endgame scale: {}", endgame_scale(board)).unwrap();
+    writeln!(out, "Halfmove clock: {}", board.halfmove_clock()).unwrap();
+    writeln!(out, "Quiet: {}", is_quiet(board)).unwrap();
+    writeln!(out, "Eval: {}", evaluate(board)).unwrap();
+    out
+}
+
+///Turn the raw network output for `board` into the final static evaluation.
+pub(crate) fn adjust_nnue_eval(board: &Board, eval: i32, scaling: &DrawScaling) -> Eval {
+    let mut eval = eval as f32;
+    if is_insufficient_material(board) {
+        eval *= scaling.insufficient_material_scale;
+    }
+    if scaling.endgame {
+        eval *= endgame_scale(board);
+    }
+    // The float to int cast saturates, and saturating again keeps the eval out of the mate range.
+    let eval = Eval::ZERO.saturating_add(Eval::cp(eval as i16));
+    match scaling.halfmove_limit {
+        Some(limit) => eval.scale_by_halfmove_clock(board.halfmove_clock(), limit),
+        None => eval
+    }
 }
 
 macro_rules! impl_math_ops {
@@ -144,3 +582,265 @@ impl_saturating_math_ops! {
     saturating_sub,
     saturating_mul
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn halfmove_clock_scales_toward_draw() {
+        assert_eq!(Eval::cp(200).scale_by_halfmove_clock(0, 100), Eval::cp(200));
+        assert_eq!(Eval::cp(200).scale_by_halfmove_clock(50, 100), Eval::cp(100));
+        assert_eq!(Eval::cp(-200).scale_by_halfmove_clock(150, 100), Eval::DRAW);
+        assert_eq!(Eval::cp(200).scale_by_halfmove_clock(0, 0), Eval::DRAW);
+        assert_eq!(Eval::mate_in(3).scale_by_halfmove_clock(99, 100), Eval::mate_in(3));
+    }
+
+    #[test]
+    fn near_limit_halfmove_clock_scores_closer_to_zero() {
+        let fresh = evaluate_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap().as_cp().unwrap();
+        let near_limit = evaluate_fen("4k3/8/8/8/8/8/8/3QK3 w - - 95 100").unwrap().as_cp().unwrap();
+        assert!(fresh > 0);
+        assert_eq!(near_limit, fresh * 5 / 100);
+    }
+
+    #[test]
+    fn single_repetition_is_a_draw() {
+        let mut board: Board = "4k3/8/8/8/8/8/8/3QK3 w - - 0 1".parse().unwrap();
+        let mut history = Vec::new();
+        for mv in ["d1d2", "e8e7", "d2d1", "e7e8"] {
+            history.push(board.hash());
+            board.play(mv.parse().unwrap());
+        }
+        assert_eq!(evaluate_with_history(&board, &history), Eval::DRAW);
+        assert!(evaluate_with_history(&board, &history[1..]) > Eval::DRAW);
+    }
+
+    #[test]
+    fn hanging_queen_is_not_quiet() {
+        let board = "4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1".parse().unwrap();
+        assert!(!is_quiet(&board));
+    }
+
+    #[test]
+    fn closed_position_is_quiet() {
+        let board = "4k3/8/1p1p1p2/pPpPpPp1/P1P1P1P1/8/8/4K3 w - - 0 1".parse().unwrap();
+        assert!(is_quiet(&board));
+    }
+
+    const SYMMETRY_FENS: &[&str] = &[
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "4k3/8/8/8/8/8/4P3/4K3 b - - 5 40",
+        "6k1/5ppp/8/8/8/8/5PPP/3R2K1 b - - 0 1",
+        "4k3/8/8/8/8/8/8/3QK3 w - - 30 50"
+    ];
+
+    #[test]
+    fn mirrored_positions_evaluate_the_same() {
+        let fens = SYMMETRY_FENS.iter()
+            .map(|fen| fen.to_string())
+            .chain(crate::tune::sample_positions(6, 200, 60).into_iter().map(|sample| sample.fen));
+        for fen in fens {
+            let board: Board = fen.parse().unwrap();
+            let mirrored = mirror_board(&board);
+            assert_eq!(mirror_board(&mirrored), board, "{}", fen);
+            assert_eq!(evaluate(&board), evaluate(&mirrored), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn win_probability() {
+        assert_eq!(Eval::ZERO.win_probability(), 0.5);
+        assert!(Eval::cp(2000).win_probability() > 0.99);
+        assert!(Eval::cp(-2000).win_probability() < 0.01);
+        assert_eq!(Eval::mate_in(1).win_probability(), 1.0);
+        assert_eq!(Eval::mated_in(1).win_probability(), 0.0);
+        for cp in [-1000, -115, -1, 0, 1, 50, 115, 1000] {
+            let eval = Eval::cp(cp);
+            assert_eq!(Eval::from_win_probability(eval.win_probability()), eval);
+        }
+        assert_eq!(Eval::from_win_probability(1.0).kind(), EvalKind::Centipawn(Eval::MAX_MATE_IN.0 - 1));
+    }
+
+    #[test]
+    fn rook_vs_minor_scale_configs() {
+        let scale = |fen: &str| rook_vs_minor_scale(&fen.parse().unwrap());
+        assert_eq!(scale("4k3/8/8/3n4/8/8/8/R3K3 w - - 0 1"), ROOK_VS_MINOR_DRAW_SCALE);
+        assert_eq!(scale("4k3/8/8/3b4/8/8/8/R3K3 w - - 0 1"), ROOK_VS_MINOR_DRAW_SCALE);
+        assert_eq!(scale("r3k3/8/8/3N4/8/8/8/4K3 w - - 0 1"), ROOK_VS_MINOR_DRAW_SCALE);
+        assert_eq!(scale("r3k3/8/8/3B4/8/8/8/4K3 b - - 0 1"), ROOK_VS_MINOR_DRAW_SCALE);
+        // Pawns, extra pieces, or both pieces on one side are left alone.
+        assert_eq!(scale("4k3/8/8/3n4/8/8/P7/R3K3 w - - 0 1"), 1.0);
+        assert_eq!(scale("4k3/8/8/3n4/8/8/8/RN2K3 w - - 0 1"), 1.0);
+        assert_eq!(scale("4k3/8/8/8/8/8/8/RN2K3 w - - 0 1"), 1.0);
+        assert_eq!(scale("r3k3/8/8/8/8/8/8/R3K3 w - - 0 1"), 1.0);
+        assert_eq!(scale("4k3/8/8/3n4/8/8/8/B3K3 w - - 0 1"), 1.0);
+        assert_eq!(scale("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"), 1.0);
+    }
+
+    #[test]
+    fn huge_network_output_is_not_mate() {
+        let board = Board::default();
+        for raw in [i32::MAX, 100_000, i16::MAX as i32, -(i16::MAX as i32), -100_000, i32::MIN] {
+            for scaling in [DrawScaling::NONE, DrawScaling::STANDARD] {
+                let eval = adjust_nnue_eval(&board, raw, &scaling);
+                assert!(eval.as_cp().is_some(), "{} {:?}", raw, eval);
+            }
+        }
+        let eval = adjust_nnue_eval(&board, i32::MAX, &DrawScaling::NONE);
+        assert_eq!(eval, Eval::cp(Eval::MAX_MATE_IN.0 - 1));
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let insufficient = |fen: &str| is_insufficient_material(&fen.parse().unwrap());
+        assert!(insufficient("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(insufficient("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1"));
+        assert!(insufficient("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        // Bishops on the same colored squares can never help each other mate.
+        assert!(insufficient("2b1k3/8/8/8/8/8/8/3BK3 w - - 0 1"));
+        assert!(!insufficient("2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+        assert!(insufficient("4k3/8/8/8/8/8/8/B1B1K3 w - - 0 1"));
+        assert!(!insufficient("4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1"));
+        assert!(!insufficient("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"));
+    }
+
+    #[test]
+    fn rook_endgame_scale_configs() {
+        let scale = |fen: &str| rook_endgame_scale(&fen.parse().unwrap());
+        // The defending king in front of the pawn, with each color attacking.
+        assert_eq!(scale("4k3/8/8/4P3/8/8/r7/R5K1 w - - 0 1"), ROOK_ENDGAME_DRAW_SCALE);
+        assert_eq!(scale("6k1/8/8/8/4p3/8/r7/R3K3 w - - 0 1"), ROOK_ENDGAME_DRAW_SCALE);
+        // The defending king beside or behind the pawn.
+        assert_eq!(scale("8/8/8/3kP3/8/8/r7/R5K1 w - - 0 1"), 1.0);
+        assert_eq!(scale("8/8/8/4P3/8/4k3/r7/R5K1 w - - 0 1"), 1.0);
+        assert_eq!(scale("6k1/8/8/8/4p3/8/r7/R2K4 w - - 0 1"), 1.0);
+        // Extra material.
+        assert_eq!(scale("4k3/8/8/4P3/8/P7/r7/R5K1 w - - 0 1"), 1.0);
+        assert_eq!(scale("4k3/8/8/4P3/8/8/r7/RN4K1 w - - 0 1"), 1.0);
+    }
+
+    #[test]
+    fn evaluate_batch_matches_serial() {
+        let boards: Vec<Board> = crate::tune::sample_positions(12, 20, 40).into_iter()
+            .map(|sample| sample.fen.parse().unwrap())
+            .collect();
+        let serial: Vec<_> = boards.iter().map(evaluate).collect();
+        assert_eq!(evaluate_batch(&boards), serial);
+        assert_eq!(evaluate_batch(&boards[..1]), serial[..1]);
+        assert!(evaluate_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn evaluate_with_status_scores_game_end() {
+        let status = |fen: &str| evaluate_with_status(&fen.parse().unwrap());
+        assert_eq!(status("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3"), Eval::mated_in(0));
+        assert_eq!(status("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), Eval::DRAW);
+        let board = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3".parse().unwrap();
+        assert_eq!(evaluate_with_status(&board), evaluate(&board));
+    }
+
+    #[test]
+    fn contempt_shifts_eval_except_dead_draws() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 3 3",
+            "4k3/8/8/8/8/8/8/3QK3 w - - 0 1"
+        ] {
+            let board = fen.parse().unwrap();
+            for contempt in [-50, 0, 25] {
+                let expected = evaluate(&board) + Eval::cp(contempt);
+                assert_eq!(evaluate_with_contempt(&board, contempt), expected, "{}", fen);
+            }
+        }
+        for fen in ["4k3/8/8/8/8/8/8/4K3 w - - 0 1", "4k3/8/8/8/8/8/8/1N2K3 b - - 0 1"] {
+            let board = fen.parse().unwrap();
+            assert_eq!(evaluate_with_contempt(&board, 50), Eval::DRAW, "{}", fen);
+            assert_eq!(evaluate_with_contempt(&board, -50), Eval::DRAW, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn evaluate_fen_rejects_bad_fens() {
+        assert!(evaluate_fen("not a fen").is_err());
+        assert!(evaluate_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1").is_err());
+        // The side not to move is in check.
+        assert!(evaluate_fen("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1").is_err());
+        assert_eq!(evaluate_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap(), evaluate(&Board::default()));
+    }
+
+    #[test]
+    fn bare_king_scale_configs() {
+        let scale = |fen: &str| {
+            let board = fen.parse().unwrap();
+            assert_eq!(endgame_scale(&board), bare_king_scale(&board), "{}", fen);
+            bare_king_scale(&board)
+        };
+        assert_eq!(scale("4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1"), KNOWN_DRAW_SCALE);
+        assert_eq!(scale("1nn1k3/8/8/8/8/8/8/4K3 w - - 0 1"), KNOWN_DRAW_SCALE);
+        // The wrong bishop, with the defending king on and next to the promotion square.
+        assert_eq!(scale("k7/8/8/P7/8/8/8/2B1K3 w - - 0 1"), KNOWN_DRAW_SCALE);
+        assert_eq!(scale("8/1k6/8/P7/P7/8/8/2B1K3 w - - 0 1"), KNOWN_DRAW_SCALE);
+        assert_eq!(scale("7k/8/8/7P/8/8/8/3BK3 w - - 0 1"), KNOWN_DRAW_SCALE);
+        assert_eq!(scale("8/6k1/8/7P/8/8/8/3BK3 w - - 0 1"), KNOWN_DRAW_SCALE);
+        assert_eq!(scale("4bk2/8/8/8/p7/8/8/K7 w - - 0 1"), KNOWN_DRAW_SCALE);
+        // The defending king away from the promotion square.
+        assert_eq!(scale("8/8/4k3/P7/8/8/8/2B1K3 w - - 0 1"), 1.0);
+        assert_eq!(scale("8/8/8/3k3P/8/8/8/3BK3 w - - 0 1"), 1.0);
+        assert_eq!(scale("4bk2/8/8/8/p7/8/8/4K3 w - - 0 1"), 1.0);
+        // The right bishop.
+        assert_eq!(scale("k7/8/8/P7/8/8/8/3BK3 w - - 0 1"), 1.0);
+        assert_eq!(scale("7k/8/8/7P/8/8/8/2B1K3 w - - 0 1"), 1.0);
+        assert_eq!(scale("3bk3/8/8/8/p7/8/8/K7 w - - 0 1"), 1.0);
+        // Pawns on both rook files, or off them.
+        assert_eq!(scale("k7/8/8/P6P/8/8/8/2B1K3 w - - 0 1"), 1.0);
+        assert_eq!(scale("k7/8/8/1P6/8/8/8/2B1K3 w - - 0 1"), 1.0);
+        // Other material against a bare king.
+        assert_eq!(scale("4k3/8/8/8/8/8/8/3QK3 w - - 0 1"), 1.0);
+        assert_eq!(scale("4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1"), 1.0);
+        assert_eq!(scale("k7/8/8/P7/8/8/8/1NB1K3 w - - 0 1"), 1.0);
+        assert_eq!(scale("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), 1.0);
+    }
+
+    #[test]
+    fn evaluate_cached_matches_evaluate() {
+        let mut cache = EvalCache::new_with_entries(NonZeroU32::new(1024).unwrap());
+        let board: Board = "4k3/8/8/8/8/8/8/3QK3 w - - 60 80".parse().unwrap();
+        assert_eq!(evaluate_cached(&board, &mut cache), evaluate(&board));
+        assert_eq!((cache.probes(), cache.hits()), (1, 0));
+        assert_eq!(evaluate_cached(&board, &mut cache), evaluate(&board));
+        assert_eq!((cache.probes(), cache.hits()), (2, 1));
+
+        // The same position four plies later, with a higher halfmove clock.
+        let mut transposed = board.clone();
+        for mv in ["d1d2", "e8e7", "d2d1", "e7e8"] {
+            transposed.play(mv.parse().unwrap());
+        }
+        assert_eq!(transposed.hash(), board.hash());
+        assert_ne!(evaluate(&transposed), evaluate(&board));
+        assert_eq!(evaluate_cached(&transposed, &mut cache), evaluate(&transposed));
+        assert_eq!((cache.probes(), cache.hits()), (3, 2));
+        assert_eq!(evaluate_cached(&board, &mut cache), evaluate(&board));
+
+        cache.clear();
+        assert_eq!((cache.probes(), cache.hits()), (0, 0));
+        assert_eq!(evaluate_cached(&transposed, &mut cache), evaluate(&transposed));
+        assert_eq!((cache.probes(), cache.hits()), (1, 0));
+    }
+
+    #[test]
+    fn display_formats_pawns() {
+        let display = |eval: Eval| eval.to_string();
+        assert_eq!(display(Eval::cp(0)), "0.00");
+        assert_eq!(display(Eval::cp(6)), "0.06");
+        assert_eq!(display(Eval::cp(-6)), "-0.06");
+        assert_eq!(display(Eval::cp(-105)), "-1.05");
+        assert_eq!(display(Eval::cp(1234)), "12.34");
+        assert_eq!(display(Eval::mate_in(1)), "M1");
+        assert_eq!(display(Eval::mate_in(3)), "M2");
+        assert_eq!(display(Eval::mated_in(2)), "-M1");
+    }
+}
@@ -2,3 +2,4 @@ pub mod search;
 pub mod eval;
 pub mod time;
 pub mod nnue;
+pub mod tune;
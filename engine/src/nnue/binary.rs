@@ -0,0 +1,257 @@
+use std::alloc::{alloc_zeroed, handle_alloc_error, Layout};
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind, Read, Result, Write};
+use std::path::Path;
+
+use super::*;
+
+const MAGIC: [u8; 4] = *b"TBNN";
+
+///The version of the binary format, bumped whenever the layout changes.
+const VERSION: u32 = 3;
+
+trait LeBytes: Sized {
+    const SIZE: usize;
+
+    fn from_le_slice(bytes: &[u8]) -> Self;
+
+    fn write_le(&self, writer: &mut impl Write) -> Result<()>;
+}
+
+macro_rules! impl_le_bytes {
+    ($($type:ty),*) => {
+        $(impl LeBytes for $type {
+            const SIZE: usize = std::mem::size_of::<$type>();
+
+            fn from_le_slice(bytes: &[u8]) -> Self {
+                Self::from_le_bytes(bytes.try_into().unwrap())
+            }
+
+            fn write_le(&self, writer: &mut impl Write) -> Result<()> {
+                writer.write_all(&self.to_le_bytes())
+            }
+        })*
+    };
+}
+
+impl_le_bytes!(u8, i8, i16, i32, u32);
+
+fn read_values<'v, T: LeBytes + 'v>(
+    reader: &mut impl Read,
+    section: &str,
+    values: impl IntoIterator<Item=&'v mut T>
+) -> Result<()> {
+    let mut buffer = [0; 4];
+    let buffer = &mut buffer[..T::SIZE];
+    for value in values {
+        reader.read_exact(buffer).map_err(|e| match e.kind() {
+            ErrorKind::UnexpectedEof => Error::new(
+                ErrorKind::InvalidData,
+                format!("unexpected end of file while reading {}", section)
+            ),
+            _ => e
+        })?;
+        *value = T::from_le_slice(buffer);
+    }
+    Ok(())
+}
+
+fn write_values<'v, T: LeBytes + 'v>(
+    writer: &mut impl Write,
+    values: impl IntoIterator<Item=&'v T>
+) -> Result<()> {
+    for value in values {
+        value.write_le(writer)?;
+    }
+    Ok(())
+}
+
+impl<const INPUTS: usize> Nnue<INPUTS> {
+    ///Read a network in the binary format.
+    ///All values are little-endian and written in order without padding:
+    ///- Header: the magic bytes `TBNN`, then the format version, `INPUTS`, `FT_OUT` and `L1_OUT` as `u32`s,
+    ///  then the activation range as an `i8`, then the weight scale and output scale as `i32`s.
+    ///- `ft` weights: `INPUTS * FT_OUT` `i16`s, in `[input][output]` order.
+    ///- `ft` biases: `FT_OUT` `i16`s.
+    ///- `l1` weights: `L1_OUT * FT_OUT * 2` `i8`s, in `[output][input]` order.
+    ///- `l1` biases: `L1_OUT` `i32`s.
+    ///
+    ///# Errors
+    ///Errors with [`ErrorKind::InvalidData`] if the format version is unknown,
+    ///if the header does not match this network,
+    ///if any of the scales are not positive, or if the data is too short or has trailing bytes.
+    ///
+    ///The network is read straight into a heap allocation,
+    ///since feature sets with many inputs are too large for the stack.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Box<Self>> {
+        let mut magic = [0; 4];
+        read_values(&mut reader, "magic", &mut magic)?;
+        if magic != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a Tantabus network"));
+        }
+        let mut version = 0;
+        read_values(&mut reader, "header", [&mut version])?;
+        if version != VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, format!(
+                "unsupported network format version {}, expected version {}",
+                version, VERSION
+            )));
+        }
+        let mut dimensions = [0; 3];
+        read_values(&mut reader, "header", &mut dimensions)?;
+        let expected = Self::dimensions();
+        if dimensions != expected {
+            return Err(Error::new(ErrorKind::InvalidData, format!(
+                "network dimensions {:?} do not match expected dimensions {:?}",
+                dimensions, expected
+            )));
+        }
+        let mut activation_range = 0;
+        read_values(&mut reader, "header", [&mut activation_range])?;
+        let mut scales = [0; 2];
+        read_values(&mut reader, "header", &mut scales)?;
+        let [weight_scale, output_scale] = scales;
+        if activation_range <= 0 || weight_scale <= 0 || output_scale <= 0 {
+            return Err(Error::new(ErrorKind::InvalidData, format!(
+                "activation range {}, weight scale {} and output scale {} must be positive",
+                activation_range, weight_scale, output_scale
+            )));
+        }
+        let mut nnue = Self::new_zeroed();
+        nnue.activation_range = activation_range;
+        nnue.weight_scale = weight_scale;
+        nnue.output_scale = output_scale;
+        read_values(&mut reader, "ft weights", nnue.ft.weights.iter_mut().flatten())?;
+        read_values(&mut reader, "ft biases", &mut nnue.ft.biases)?;
+        read_values(&mut reader, "l1 weights", nnue.l1.weights.iter_mut().flatten())?;
+        read_values(&mut reader, "l1 biases", &mut nnue.l1.biases)?;
+        if reader.read(&mut [0])? != 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "trailing data after l1 biases"));
+        }
+        Ok(nnue)
+    }
+
+    ///Write the network in the binary format read by [`Nnue::from_reader`].
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        write_values(&mut writer, &MAGIC)?;
+        write_values(&mut writer, [&VERSION])?;
+        write_values(&mut writer, &Self::dimensions())?;
+        write_values(&mut writer, [&self.activation_range])?;
+        write_values(&mut writer, [&self.weight_scale, &self.output_scale])?;
+        write_values(&mut writer, self.ft.weights.iter().flatten())?;
+        write_values(&mut writer, &self.ft.biases)?;
+        write_values(&mut writer, self.l1.weights.iter().flatten())?;
+        write_values(&mut writer, &self.l1.biases)?;
+        Ok(())
+    }
+
+    ///Allocate a network with every value zeroed directly on the heap.
+    pub(super) fn new_zeroed() -> Box<Self> {
+        let layout = Layout::new::<Self>();
+        // SAFETY: The layout is not zero sized, and the allocation is checked for failure.
+        // Every field of Nnue is an integer or an array of integers, so all zeros is a valid value.
+        unsafe {
+            let nnue = alloc_zeroed(layout) as *mut Self;
+            if nnue.is_null() {
+                handle_alloc_error(layout);
+            }
+            Box::from_raw(nnue)
+        }
+    }
+
+    fn dimensions() -> [u32; 3] {
+        [INPUTS as u32, FT_OUT as u32, L1_OUT as u32]
+    }
+
+    ///Read a network in the binary format from a file.
+    ///See [`Nnue::from_reader`] for the format.
+    pub fn load(path: &Path) -> Result<Box<Self>> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_bytes() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        Nnue::DEFAULT.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn read_error(bytes: &[u8]) -> Error {
+        Nnue::<FEATURES>::from_reader(bytes).unwrap_err()
+    }
+
+    #[test]
+    fn default_round_trips() {
+        let bytes = default_bytes();
+        assert_eq!(bytes.len(), 49313);
+        assert!(*Nnue::<FEATURES>::from_reader(&bytes[..]).unwrap() == Nnue::DEFAULT);
+    }
+
+    #[test]
+    fn truncated_file_is_invalid() {
+        let bytes = default_bytes();
+        for len in [0, 3, 8, 20, 100, bytes.len() - 1] {
+            assert_eq!(read_error(&bytes[..len]).kind(), ErrorKind::InvalidData, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn trailing_bytes_are_invalid() {
+        let mut bytes = default_bytes();
+        bytes.push(0);
+        assert_eq!(read_error(&bytes).kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn bad_magic_is_invalid() {
+        let mut bytes = default_bytes();
+        bytes[0] = b'X';
+        assert_eq!(read_error(&bytes).kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn unknown_version_is_invalid() {
+        let mut bytes = default_bytes();
+        bytes[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        let error = read_error(&bytes);
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().contains("version"));
+    }
+
+    #[test]
+    fn wrong_dimensions_are_invalid() {
+        let bytes = default_bytes();
+        let error = Nnue::<HALF_KA_FEATURES>::from_reader(&bytes[..]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        let mut bytes = bytes;
+        bytes[12..16].copy_from_slice(&(FT_OUT as u32 + 1).to_le_bytes());
+        assert_eq!(read_error(&bytes).kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn half_ka_loads_on_a_small_stack() {
+        // A HalfKA network is about 1.5 MB, far larger than this thread's stack.
+        std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(|| {
+                let mut nnue = Nnue::<HALF_KA_FEATURES>::new_zeroed();
+                nnue.activation_range = ACTIVATION_RANGE;
+                nnue.weight_scale = WEIGHT_SCALE;
+                nnue.output_scale = OUTPUT_SCALE;
+                for (i, weight) in nnue.ft.weights.iter_mut().flatten().enumerate() {
+                    *weight = i as i16;
+                }
+                let mut bytes = Vec::new();
+                nnue.write(&mut bytes).unwrap();
+                let read = Nnue::<HALF_KA_FEATURES>::from_reader(&bytes[..]).unwrap();
+                assert!(read == nnue);
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+use cozy_chess::*;
+
+pub const FEATURES: usize = Color::NUM * Piece::NUM * Square::NUM;
+pub const KING_BUCKETS: usize = 32;
+pub const HALF_KA_FEATURES: usize = KING_BUCKETS * FEATURES;
+
+macro_rules! index {
+    ($([$index:expr; $count:expr])*) => {{
+        let mut index = 0;
+        $(index = index * $count + $index;)*
+        index
+    }}
+}
+
+///Maps board state to feature transformer inputs.
+///`INPUTS` is the number of inputs the feature set produces.
+pub trait FeatureSet<const INPUTS: usize> {
+    ///Whether features depend on the perspective's king square.
+    ///If so, the accumulator for a perspective must be refreshed when its king moves.
+    const KING_RELATIVE: bool;
+
+    fn feature(perspective: Color, king: Square, color: Color, piece: Piece, square: Square) -> usize;
+}
+
+///The standard 768 feature set, indexed only by color, piece, and square.
+#[derive(Debug, Clone, Copy)]
+pub struct Standard;
+
+impl FeatureSet<FEATURES> for Standard {
+    const KING_RELATIVE: bool = false;
+
+    fn feature(perspective: Color, _: Square, color: Color, piece: Piece, square: Square) -> usize {
+        feature(perspective, color, piece, square)
+    }
+}
+
+// CITE: HalfKA feature set.
+// https://github.com/glinscott/nnue-pytorch/blob/master/docs/nnue.md#halfka
+///HalfKA style feature set. The standard features are additionally indexed by
+///one of 32 buckets for the perspective's king square, mirrored so the king is
+///always on the queenside files.
+#[derive(Debug, Clone, Copy)]
+pub struct HalfKa;
+
+impl FeatureSet<HALF_KA_FEATURES> for HalfKa {
+    const KING_RELATIVE: bool = true;
+
+    fn feature(perspective: Color, king: Square, color: Color, piece: Piece, square: Square) -> usize {
+        let mirror = king.file() > File::D;
+        let king = orient_square(perspective, mirror, king);
+        let bucket = king.rank() as usize * 4 + king.file() as usize;
+        index! {
+            [bucket; KING_BUCKETS]
+            [oriented_feature(perspective, color, piece, orient_square(perspective, mirror, square)); FEATURES]
+        }
+    }
+}
+
+///Orient `square` so that `perspective`'s pieces start on the first ranks,
+///additionally mirroring the files if `mirror` is set.
+///Feature sets orient every square through this so that they can't disagree on orientation.
+pub fn orient_square(perspective: Color, mirror: bool, mut square: Square) -> Square {
+    if perspective == Color::Black {
+        square = square.flip_rank();
+    }
+    if mirror {
+        square = square.flip_file();
+    }
+    square
+}
+
+pub fn feature(perspective: Color, color: Color, piece: Piece, square: Square) -> usize {
+    oriented_feature(perspective, color, piece, orient_square(perspective, false, square))
+}
+
+///The standard feature for a square already oriented with [`orient_square`].
+fn oriented_feature(perspective: Color, mut color: Color, piece: Piece, square: Square) -> usize {
+    if perspective == Color::Black {
+        color = !color;
+    }
+    index! {
+        [color as usize; Color::NUM]
+        [piece as usize; Piece::NUM]
+        [square as usize; Square::NUM]
+    }
+}
+
+///The inverse of [`feature`].
+///# Panics
+///Panics if `index` is not less than [`FEATURES`].
+pub fn feature_inverse(perspective: Color, index: usize) -> (Color, Piece, Square) {
+    let square = Square::index(index % Square::NUM);
+    let piece = Piece::index(index / Square::NUM % Piece::NUM);
+    let mut color = Color::index(index / (Square::NUM * Piece::NUM));
+    if perspective == Color::Black {
+        color = !color;
+    }
+    (color, piece, orient_square(perspective, false, square))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feature_inverse_round_trips() {
+        for &perspective in &Color::ALL {
+            for index in 0..FEATURES {
+                let (color, piece, square) = feature_inverse(perspective, index);
+                assert_eq!(feature(perspective, color, piece, square), index);
+            }
+            for &color in &Color::ALL {
+                for &piece in &Piece::ALL {
+                    for &square in &Square::ALL {
+                        let index = feature(perspective, color, piece, square);
+                        assert_eq!(feature_inverse(perspective, index), (color, piece, square));
+                    }
+                }
+            }
+        }
+    }
+}
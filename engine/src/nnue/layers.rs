@@ -2,7 +2,7 @@ use num_traits::PrimInt;
 
 use super::ops::*;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Linear<W, B, const INPUTS: usize, const OUTPUTS: usize> {
     pub weights: [[W; INPUTS]; OUTPUTS],
     pub biases: [B; OUTPUTS]
@@ -21,7 +21,7 @@ impl<
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BitLinear<WB, const INPUTS: usize, const OUTPUTS: usize> {
     pub weights: [[WB; OUTPUTS]; INPUTS],
     pub biases: [WB; OUTPUTS]
@@ -33,7 +33,7 @@ impl<
     const OUTPUTS: usize
 > BitLinear<WB, INPUTS, OUTPUTS>
 where
-    [WB; OUTPUTS]: VecAdd + VecSub {
+    [WB; OUTPUTS]: VecAdd + VecSub + VecAddPair {
     pub fn empty(&self, outputs: &mut [WB; OUTPUTS]) {
         *outputs = self.biases;
     }
@@ -45,4 +45,9 @@ where
     pub fn sub(&self, index: usize, outputs: &mut [WB; OUTPUTS]) {
         outputs.vec_sub(&self.weights[index]);
     }
+
+    ///Add the weights of `indices[0]` to `first` and of `indices[1]` to `second` in one pass.
+    pub fn add_pair(&self, indices: [usize; 2], first: &mut [WB; OUTPUTS], second: &mut [WB; OUTPUTS]) {
+        first.vec_add_pair(&self.weights[indices[0]], second, &self.weights[indices[1]]);
+    }
 }
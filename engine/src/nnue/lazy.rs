@@ -0,0 +1,203 @@
+use std::cell::{Ref, RefCell};
+
+use arrayvec::ArrayVec;
+use cozy_chess::*;
+
+use super::*;
+
+const MAX_PENDING_UPDATES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FeatureUpdate {
+    color: Color,
+    piece: Piece,
+    square: Square,
+    add: bool
+}
+
+#[derive(Debug)]
+struct LazyState<'m, F, const INPUTS: usize> {
+    state: NnueState<'m, F, INPUTS>,
+    pending: ArrayVec<FeatureUpdate, MAX_PENDING_UPDATES>
+}
+
+impl<F: FeatureSet<INPUTS>, const INPUTS: usize> LazyState<'_, F, INPUTS> {
+    fn flush(&mut self) {
+        for update in self.pending.drain(..) {
+            if update.add {
+                self.state.add(update.color, update.piece, update.square);
+            } else {
+                self.state.sub(update.color, update.piece, update.square);
+            }
+        }
+    }
+}
+
+///An [`NnueState`] that buffers feature updates and only applies them once the
+///accumulator is read, so states that are never evaluated skip the work entirely.
+///
+///Buffering adds overhead to every update that is eventually applied, so this can
+///only win when most states are never evaluated. Search evaluates nearly every node
+///and uses [`NnueState`] directly. `tantabus-uci bench-nnue` compares the two.
+#[derive(Debug)]
+pub struct LazyNnueState<'m, F: FeatureSet<INPUTS> = Standard, const INPUTS: usize = FEATURES> {
+    inner: RefCell<LazyState<'m, F, INPUTS>>
+}
+
+impl<'m, F: FeatureSet<INPUTS>, const INPUTS: usize> LazyNnueState<'m, F, INPUTS> {
+    pub fn new(state: NnueState<'m, F, INPUTS>) -> Self {
+        Self {
+            inner: RefCell::new(LazyState {
+                state,
+                pending: ArrayVec::new()
+            })
+        }
+    }
+
+    ///Apply any pending feature updates and return the underlying state.
+    ///Other borrows of the state may still be alive, which is fine since
+    ///updates can only be pushed through `&mut self`.
+    pub fn state(&self) -> Ref<'_, NnueState<'m, F, INPUTS>> {
+        self.flush();
+        Ref::map(self.inner.borrow(), |inner| &inner.state)
+    }
+
+    fn flush(&self) {
+        // Only borrow mutably when there is work to do, so reading while
+        // holding a previous borrow doesn't panic.
+        if !self.inner.borrow().pending.is_empty() {
+            self.inner.borrow_mut().flush();
+        }
+    }
+
+    ///Apply any pending feature updates and return the accumulator.
+    pub fn accumulator(&self) -> Ref<'_, [[i16; FT_OUT]; Color::NUM]> {
+        Ref::map(self.state(), |state| state.accumulator())
+    }
+
+    fn push_update(&mut self, update: FeatureUpdate) {
+        let inner = self.inner.get_mut();
+        let inverse = FeatureUpdate {
+            add: !update.add,
+            ..update
+        };
+        // Adding and removing the same feature cancels out.
+        if let Some(index) = inner.pending.iter().position(|&u| u == inverse) {
+            inner.pending.swap_remove(index);
+            return;
+        }
+        if inner.pending.is_full() {
+            inner.flush();
+        }
+        inner.pending.push(update);
+    }
+
+    pub fn add(&mut self, color: Color, piece: Piece, square: Square) {
+        self.push_update(FeatureUpdate {
+            color,
+            piece,
+            square,
+            add: true
+        });
+    }
+
+    pub fn sub(&mut self, color: Color, piece: Piece, square: Square) {
+        self.push_update(FeatureUpdate {
+            color,
+            piece,
+            square,
+            add: false
+        });
+    }
+
+    ///Lazy equivalent of [`NnueState::play`].
+    pub fn play(&mut self, board: &Board, mv: Move) {
+        let inner = self.inner.get_mut();
+        // Saved states must not have pending updates, or they would be applied again
+        // by every child that reads the accumulator.
+        inner.flush();
+        inner.state.history.push((inner.state.accumulator, inner.state.kings));
+        move_updates(board, mv, |color, piece, square, add| {
+            self.push_update(FeatureUpdate {
+                color,
+                piece,
+                square,
+                add
+            });
+        });
+        if F::KING_RELATIVE && board.piece_on(mv.from) == Some(Piece::King) {
+            let inner = self.inner.get_mut();
+            inner.flush();
+            let mut child = board.clone();
+            child.play_unchecked(mv);
+            inner.state.refresh(board.side_to_move(), &child);
+        }
+    }
+
+    ///Lazy equivalent of [`NnueState::unplay`].
+    pub fn unplay(&mut self) {
+        let inner = self.inner.get_mut();
+        inner.pending.clear();
+        inner.state.unplay();
+    }
+
+    pub fn evaluate(&self, side_to_move: Color) -> i32 {
+        self.state().evaluate(side_to_move)
+    }
+}
+
+impl<F: FeatureSet<INPUTS> + Clone, const INPUTS: usize> Clone for LazyNnueState<'_, F, INPUTS> {
+    fn clone(&self) -> Self {
+        // Flush first so the pending updates are not duplicated into the clone.
+        Self::new(self.state().clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluate_while_accumulator_is_borrowed() {
+        let board = Board::default();
+        let mut lazy = LazyNnueState::new(Nnue::DEFAULT.state_from_board(&board));
+        let mv = "e2e4".parse().unwrap();
+        lazy.play(&board, mv);
+        let accumulator = lazy.accumulator();
+        let eval = lazy.evaluate(Color::Black);
+        let clone = lazy.clone();
+
+        let mut eager = Nnue::DEFAULT.state_from_board(&board);
+        eager.play(&board, mv);
+        assert_eq!(*accumulator, *eager.accumulator());
+        assert_eq!(eval, eager.evaluate(Color::Black));
+        assert_eq!(*clone.accumulator(), *eager.accumulator());
+    }
+
+    #[test]
+    fn add_then_sub_cancels() {
+        let board = Board::default();
+        let mut lazy = LazyNnueState::new(Nnue::DEFAULT.state_from_board(&board));
+        lazy.add(Color::White, Piece::Queen, Square::D4);
+        lazy.sub(Color::White, Piece::Queen, Square::D4);
+        assert!(lazy.inner.get_mut().pending.is_empty());
+        assert_eq!(*lazy.accumulator(), *Nnue::DEFAULT.state_from_board(&board).accumulator());
+    }
+
+    #[test]
+    fn half_ka_king_moves_match_eager() {
+        let half_ka = crate::nnue::tests::random_half_ka(10);
+        let board: Board = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1".parse().unwrap();
+        for mv in ["e1h1", "e1a1", "e1d1", "e1f2", "a1a8"] {
+            let mv = mv.parse().unwrap();
+            let mut lazy = LazyNnueState::new(half_ka.state_from_board_with::<HalfKa>(&board));
+            let before = *lazy.accumulator();
+            lazy.play(&board, mv);
+            let mut child = board.clone();
+            child.play(mv);
+            assert!(lazy.state().verify(&child), "{}", mv);
+            lazy.unplay();
+            assert_eq!(*lazy.accumulator(), before, "{}", mv);
+        }
+    }
+}
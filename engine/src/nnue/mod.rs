@@ -1,67 +1,195 @@
 use cozy_chess::*;
 
+use std::marker::PhantomData;
+use std::str::FromStr;
+
 mod ops;
 mod layers;
+mod features;
+mod binary;
+mod stack;
+mod lazy;
+mod stats;
+mod quantize;
 
 use self::layers::*;
 use self::ops::*;
+pub use self::features::*;
+pub use self::stack::*;
+pub use self::lazy::*;
+pub use self::stats::*;
+pub use self::quantize::*;
 
-const FEATURES: usize = 768;
 const FT_OUT: usize = 32;
 const L1_OUT: usize = 1;
 
 const ACTIVATION_RANGE: i8 = 127;
-const WEIGHT_SCALE: i8 = 64;
-const OUTPUT_SCALE: i32 = 115;
+const WEIGHT_SCALE: i32 = 64;
+pub(crate) const OUTPUT_SCALE: i32 = 115;
 
-#[derive(Debug, Clone)]
-pub struct Nnue {
-    pub ft: BitLinear<i16, FEATURES, FT_OUT>,
-    pub l1: Linear<i8, i32, {FT_OUT * Color::NUM}, L1_OUT>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nnue<const INPUTS: usize = FEATURES> {
+    pub ft: BitLinear<i16, INPUTS, FT_OUT>,
+    pub l1: Linear<i8, i32, {FT_OUT * Color::NUM}, L1_OUT>,
+    ///The upper bound the feature transformer outputs are clipped to.
+    ///This is also the quantization scale of the activations.
+    pub activation_range: i8,
+    ///The quantization scale of the `l1` weights.
+    pub weight_scale: i32,
+    ///The scale from the dequantized network output to centipawns.
+    pub output_scale: i32
+}
+
+///The layers of the embedded network, as written in `model.txt`.
+struct Layers {
+    ft: BitLinear<i16, FEATURES, FT_OUT>,
+    l1: Linear<i8, i32, {FT_OUT * Color::NUM}, L1_OUT>
 }
 
 impl Nnue {
-    pub const DEFAULT: Self = include!("model.txt");
+    pub const DEFAULT: Self = {
+        use self::Layers as Nnue;
+        let layers = include!("model.txt");
+        Self {
+            ft: layers.ft,
+            l1: layers.l1,
+            activation_range: ACTIVATION_RANGE,
+            weight_scale: WEIGHT_SCALE,
+            output_scale: OUTPUT_SCALE
+        }
+    };
 
     pub fn new_state(&self) -> NnueState<'_> {
-        let mut accumulator = [[0; FT_OUT]; Color::NUM];
+        self.new_state_with(&Board::default())
+    }
+
+    ///Create a state with the accumulator built from every piece on `board`.
+    pub fn state_from_board(&self, board: &Board) -> NnueState<'_> {
+        self.state_from_board_with(board)
+    }
+
+    ///Parse `fen` and evaluate it from scratch, from the side to move's perspective.
+    pub fn evaluate_fen(&self, fen: &str) -> Result<i32, <Board as FromStr>::Err> {
+        let board = fen.parse::<Board>()?;
+        Ok(self.state_from_board(&board).evaluate(board.side_to_move()))
+    }
+
+    ///Evaluate each board from scratch, from the side to move's perspective.
+    ///The boards are split evenly between the available threads.
+    pub fn evaluate_batch(&self, boards: &[Board]) -> Vec<i32> {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        self.evaluate_batch_with_threads(boards, threads)
+    }
+
+    fn evaluate_batch_with_threads(&self, boards: &[Board], threads: usize) -> Vec<i32> {
+        let chunk_size = boards.len().div_ceil(threads).max(1);
+        let evaluate = |boards: &[Board]| boards.iter()
+            .map(|board| self.state_from_board(board).evaluate(board.side_to_move()))
+            .collect::<Vec<_>>();
+        if threads == 1 {
+            return evaluate(boards);
+        }
+        std::thread::scope(|scope| {
+            let handles = boards.chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || evaluate(chunk)))
+                .collect::<Vec<_>>();
+            handles.into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+}
+
+impl<const INPUTS: usize> Nnue<INPUTS> {
+    ///Create an empty state for a feature set.
+    ///King relative feature sets use the king squares of `board`.
+    pub fn new_state_with<F: FeatureSet<INPUTS>>(&self, board: &Board) -> NnueState<'_, F, INPUTS> {
+        let mut accumulator = Accumulator([[0; FT_OUT]; Color::NUM]);
         self.ft.empty(&mut accumulator[Color::White as usize]);
         self.ft.empty(&mut accumulator[Color::Black as usize]);
         NnueState {
             model: self,
-            accumulator
+            accumulator,
+            history: Vec::new(),
+            kings: [board.king(Color::White), board.king(Color::Black)],
+            _features: PhantomData
         }
     }
+
+    ///Convert a raw network output to centipawns.
+    ///The raw output is quantized by both the weight scale and the activation range.
+    pub fn raw_to_cp(&self, raw: i32) -> i32 {
+        raw * self.output_scale / (self.weight_scale * self.activation_range as i32)
+    }
+
+    ///Create a state for a feature set with the accumulator built from every piece on `board`.
+    pub fn state_from_board_with<F: FeatureSet<INPUTS>>(&self, board: &Board) -> NnueState<'_, F, INPUTS> {
+        let mut state = self.new_state_with(board);
+        state.refresh_both(board);
+        state
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct NnueState<'m> {
-    model: &'m Nnue,
-    accumulator: [[i16; FT_OUT]; Color::NUM]
+///The feature transformer outputs for both perspectives.
+///Aligned so that each perspective's outputs start on a 32 byte boundary for SIMD loads and stores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C, align(32))]
+struct Accumulator([[i16; FT_OUT]; Color::NUM]);
+
+impl std::ops::Deref for Accumulator {
+    type Target = [[i16; FT_OUT]; Color::NUM];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
 }
 
-pub fn feature(perspective: Color, mut color: Color, piece: Piece, mut square: Square) -> usize {
-    if perspective == Color::Black {
-        square = square.flip_rank();
-        color = !color;
+impl std::ops::DerefMut for Accumulator {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
     }
-    macro_rules! index {
-        ($([$index:expr; $count:expr])*) => {{
-            let mut index = 0;
-            $(index = index * $count + $index;)*
-            index
-        }}
+}
+
+#[derive(Debug, Clone)]
+pub struct NnueState<'m, F = Standard, const INPUTS: usize = FEATURES> {
+    model: &'m Nnue<INPUTS>,
+    accumulator: Accumulator,
+    history: Vec<(Accumulator, [Square; Color::NUM])>,
+    kings: [Square; Color::NUM],
+    _features: PhantomData<F>
+}
+
+///Call `update` for every feature that is added or removed by playing `mv` on `board`.
+fn move_updates(board: &Board, mv: Move, mut update: impl FnMut(Color, Piece, Square, bool)) {
+    let color = board.side_to_move();
+    let moved = board.piece_on(mv.from).unwrap();
+    if board.color_on(mv.to) == Some(color) {
+        // Castling is encoded as the king capturing its own rook.
+        let (king_file, rook_file) = if mv.to.file() > mv.from.file() {
+            (File::G, File::F)
+        } else {
+            (File::C, File::D)
+        };
+        let rank = mv.from.rank();
+        update(color, Piece::King, mv.from, false);
+        update(color, Piece::Rook, mv.to, false);
+        update(color, Piece::King, Square::new(king_file, rank), true);
+        update(color, Piece::Rook, Square::new(rook_file, rank), true);
+        return;
     }
-    index! {
-        [color as usize; Color::NUM]
-        [piece as usize; Piece::NUM]
-        [square as usize; Square::NUM]
+    if let Some(captured) = board.piece_on(mv.to) {
+        update(!color, captured, mv.to, false);
+    } else if moved == Piece::Pawn && mv.from.file() != mv.to.file() {
+        // En passant. The captured pawn is beside the moving pawn, not on the target square.
+        update(!color, Piece::Pawn, Square::new(mv.to.file(), mv.from.rank()), false);
     }
+    update(color, moved, mv.from, false);
+    update(color, mv.promotion.unwrap_or(moved), mv.to, true);
 }
 
-impl<'s> NnueState<'s> {
-    pub fn model(&self) -> &Nnue {
-        &self.model
+impl<'s, F: FeatureSet<INPUTS>, const INPUTS: usize> NnueState<'s, F, INPUTS> {
+    pub fn model(&self) -> &Nnue<INPUTS> {
+        self.model
     }
 
     pub fn accumulator(&self) -> &[[i16; FT_OUT]; Color::NUM] {
@@ -70,27 +198,428 @@ impl<'s> NnueState<'s> {
 
     pub fn add(&mut self, color: Color, piece: Piece, square: Square) {
         for &perspective in &Color::ALL {
-            let feature = feature(perspective, color, piece, square);
+            let feature = F::feature(perspective, self.kings[perspective as usize], color, piece, square);
             self.model.ft.add(feature, &mut self.accumulator[perspective as usize]);
         }
     }
 
     pub fn sub(&mut self, color: Color, piece: Piece, square: Square) {
         for &perspective in &Color::ALL {
-            let feature = feature(perspective, color, piece, square);
+            let feature = F::feature(perspective, self.kings[perspective as usize], color, piece, square);
             self.model.ft.sub(feature, &mut self.accumulator[perspective as usize]);
         }
     }
 
+    ///Rebuild the accumulator for `perspective` from scratch.
+    pub fn refresh(&mut self, perspective: Color, board: &Board) {
+        let king = board.king(perspective);
+        self.kings[perspective as usize] = king;
+        let accumulator = &mut self.accumulator[perspective as usize];
+        self.model.ft.empty(accumulator);
+        for &color in &Color::ALL {
+            let colors = board.colors(color);
+            for &piece in &Piece::ALL {
+                let pieces = board.pieces(piece);
+                for square in pieces & colors {
+                    let feature = F::feature(perspective, king, color, piece, square);
+                    self.model.ft.add(feature, accumulator);
+                }
+            }
+        }
+    }
+
+    ///Rebuild the accumulators for both perspectives from scratch.
+    ///This scans `board` once and updates both perspectives for each piece,
+    ///instead of scanning it once per perspective like two calls to [`NnueState::refresh`].
+    ///Both weight rows for a piece are added in the same pass over the accumulators.
+    pub fn refresh_both(&mut self, board: &Board) {
+        let kings = [board.king(Color::White), board.king(Color::Black)];
+        self.kings = kings;
+        let [white, black] = &mut *self.accumulator;
+        self.model.ft.empty(white);
+        self.model.ft.empty(black);
+        for &color in &Color::ALL {
+            let colors = board.colors(color);
+            for &piece in &Piece::ALL {
+                let pieces = board.pieces(piece);
+                for square in pieces & colors {
+                    let white_feature = F::feature(Color::White, kings[0], color, piece, square);
+                    let black_feature = F::feature(Color::Black, kings[1], color, piece, square);
+                    self.model.ft.add_pair([white_feature, black_feature], white, black);
+                }
+            }
+        }
+    }
+
+    ///Apply the feature changes caused by playing `mv` on `board`.
+    ///`board` is the position before the move is played.
+    ///The previous accumulator is saved so that [`NnueState::unplay`] can restore it.
+    pub fn play(&mut self, board: &Board, mv: Move) {
+        self.history.push((self.accumulator, self.kings));
+        self.apply_move(board, mv);
+    }
+
+    ///Apply the feature changes caused by playing `mv` on `board` without saving the previous accumulator.
+    ///`board` is the position before the move is played.
+    ///Use this when the caller keeps its own copy of the previous state.
+    pub fn apply_move(&mut self, board: &Board, mv: Move) {
+        move_updates(board, mv, |color, piece, square, add| if add {
+            self.add(color, piece, square);
+        } else {
+            self.sub(color, piece, square);
+        });
+        if F::KING_RELATIVE && board.piece_on(mv.from) == Some(Piece::King) {
+            // Every feature for our perspective depends on our king square.
+            let mut child = board.clone();
+            child.play_unchecked(mv);
+            self.refresh(board.side_to_move(), &child);
+        }
+    }
+
+    ///Undo the last move applied with [`NnueState::play`].
+    pub fn unplay(&mut self) {
+        (self.accumulator, self.kings) = self.history.pop()
+            .expect("unplay called without a matching play");
+    }
+
+    ///Check that the incrementally updated accumulator matches one rebuilt from `board`.
+    ///This is as slow as [`Nnue::state_from_board`], so search only checks it with the `verify-nnue` feature.
+    pub fn verify(&self, board: &Board) -> bool {
+        let fresh = self.model.state_from_board_with::<F>(board);
+        fresh.accumulator == self.accumulator && (!F::KING_RELATIVE || fresh.kings == self.kings)
+    }
+
+    ///Evaluate from `side_to_move`'s perspective, in centipawns.
+    ///Same as [`NnueState::evaluate_cp`].
     pub fn evaluate(&self, side_to_move: Color) -> i32 {
-        let mut inputs = [[0; FT_OUT]; Color::NUM];
+        self.evaluate_cp(side_to_move)
+    }
+
+    ///Evaluate from `side_to_move`'s perspective, in centipawns.
+    pub fn evaluate_cp(&self, side_to_move: Color) -> i32 {
+        self.model.raw_to_cp(self.evaluate_raw(side_to_move))
+    }
+
+    ///Evaluate from `side_to_move`'s perspective, in the network's quantized output units.
+    ///See [`Nnue::raw_to_cp`] for the conversion to centipawns.
+    pub fn evaluate_raw(&self, side_to_move: Color) -> i32 {
+        let mut inputs = [0; FT_OUT * Color::NUM];
+        let (us, them) = split_inputs(&mut inputs);
         self.accumulator[side_to_move as usize]
-            .clipped_relu(0, ACTIVATION_RANGE, &mut inputs[0]);
+            .clipped_relu(0, self.model.activation_range, us);
         self.accumulator[(!side_to_move) as usize]
-            .clipped_relu(0, ACTIVATION_RANGE, &mut inputs[1]);
-        let inputs = bytemuck::cast(inputs);
+            .clipped_relu(0, self.model.activation_range, them);
+        self.forward(&inputs)
+    }
+
+    ///Evaluate the position after `side_to_move` passes with a null move.
+    ///A null move changes no features, so only the perspective is swapped.
+    pub fn evaluate_after_null(&self, side_to_move: Color) -> i32 {
+        self.evaluate(!side_to_move)
+    }
+
+    ///Evaluate from White's and Black's perspective, in that order.
+    ///Both perspectives share the same activated accumulators.
+    pub fn evaluate_both(&self) -> (i32, i32) {
+        let mut white_inputs = [0; FT_OUT * Color::NUM];
+        let (white, black) = split_inputs(&mut white_inputs);
+        self.accumulator[Color::White as usize]
+            .clipped_relu(0, self.model.activation_range, white);
+        self.accumulator[Color::Black as usize]
+            .clipped_relu(0, self.model.activation_range, black);
+        let mut black_inputs = [0; FT_OUT * Color::NUM];
+        let (black_us, black_them) = split_inputs(&mut black_inputs);
+        black_us.copy_from_slice(black);
+        black_them.copy_from_slice(white);
+        let white_eval = self.model.raw_to_cp(self.forward(&white_inputs));
+        let black_eval = self.model.raw_to_cp(self.forward(&black_inputs));
+        (white_eval, black_eval)
+    }
+
+    ///Run the layers after the feature transformer, returning the raw output.
+    ///`inputs` holds the activated accumulators, side to move first.
+    fn forward(&self, inputs: &[i8; FT_OUT * Color::NUM]) -> i32 {
         let mut outputs = [0; L1_OUT];
-        self.model.l1.activate(&inputs, &mut outputs);
-        outputs[0] * OUTPUT_SCALE / WEIGHT_SCALE as i32 / ACTIVATION_RANGE as i32
+        self.model.l1.activate(inputs, &mut outputs);
+        outputs[0]
+    }
+}
+
+///Split the `l1` inputs into the halves for the side to move and the other side,
+///so the feature transformer activates straight into the `l1` input.
+fn split_inputs(inputs: &mut [i8; FT_OUT * Color::NUM]) -> (&mut [i8; FT_OUT], &mut [i8; FT_OUT]) {
+    let (us, them) = inputs.split_at_mut(FT_OUT);
+    (us.try_into().unwrap(), them.try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tune::sample_positions;
+
+    ///Evaluate `board` from scratch with only the scalar kernels, as a reference for the SIMD ones.
+    fn scalar_evaluate(model: &Nnue, board: &Board, side_to_move: Color) -> i32 {
+        let mut accumulator = [model.ft.biases; Color::NUM];
+        for &perspective in &Color::ALL {
+            for &color in &Color::ALL {
+                for &piece in &Piece::ALL {
+                    for square in board.colors(color) & board.pieces(piece) {
+                        let feature = feature(perspective, color, piece, square);
+                        scalar::vec_add_i16(&mut accumulator[perspective as usize], &model.ft.weights[feature]);
+                    }
+                }
+            }
+        }
+        let mut inputs = [0; FT_OUT * Color::NUM];
+        let (us, them) = split_inputs(&mut inputs);
+        scalar::clipped_relu_i16(&accumulator[side_to_move as usize], 0, model.activation_range, us);
+        scalar::clipped_relu_i16(&accumulator[(!side_to_move) as usize], 0, model.activation_range, them);
+        model.raw_to_cp(model.l1.biases[0] + scalar::dot_i8(&inputs, &model.l1.weights[0]))
+    }
+
+    #[test]
+    fn evaluate_matches_scalar() {
+        for sample in sample_positions(0, 200, 40) {
+            let board = sample.fen.parse().unwrap();
+            let state = Nnue::DEFAULT.state_from_board(&board);
+            for &color in &Color::ALL {
+                let expected = scalar_evaluate(&Nnue::DEFAULT, &board, color);
+                assert_eq!(state.evaluate(color), expected, "{}", sample.fen);
+            }
+        }
+    }
+
+    #[test]
+    fn verify_catches_corruption() {
+        let board = Board::default();
+        let mut state = Nnue::DEFAULT.state_from_board(&board);
+        let mv = "g1f3".parse().unwrap();
+        state.play(&board, mv);
+        let mut child = board.clone();
+        child.play(mv);
+        assert!(state.verify(&child));
+        assert!(!state.verify(&board));
+        state.accumulator[Color::Black as usize][0] += 1;
+        assert!(!state.verify(&child));
+    }
+
+    #[test]
+    fn evaluate_both_matches_each_side() {
+        for sample in sample_positions(1, 100, 40) {
+            let state = Nnue::DEFAULT.state_from_board(&sample.fen.parse().unwrap());
+            let expected = (state.evaluate(Color::White), state.evaluate(Color::Black));
+            assert_eq!(state.evaluate_both(), expected, "{}", sample.fen);
+        }
+    }
+
+    #[test]
+    fn evaluate_after_null_swaps_perspective() {
+        for sample in sample_positions(2, 100, 40) {
+            let board: Board = sample.fen.parse().unwrap();
+            let Some(null) = board.null_move() else {
+                continue;
+            };
+            let state = Nnue::DEFAULT.state_from_board(&board);
+            let expected = Nnue::DEFAULT.state_from_board(&null).evaluate(null.side_to_move());
+            assert_eq!(state.evaluate_after_null(board.side_to_move()), expected, "{}", sample.fen);
+        }
+    }
+
+    #[test]
+    fn output_scale_changes_cp_linearly() {
+        let mut identity = Nnue::DEFAULT;
+        identity.output_scale = identity.weight_scale * identity.activation_range as i32;
+        let mut doubled = Nnue::DEFAULT;
+        doubled.output_scale *= 2;
+        for sample in sample_positions(3, 50, 40) {
+            let board: Board = sample.fen.parse().unwrap();
+            let side_to_move = board.side_to_move();
+            let raw = Nnue::DEFAULT.state_from_board(&board).evaluate_raw(side_to_move);
+            assert_eq!(identity.state_from_board(&board).evaluate(side_to_move), raw);
+            let cp = Nnue::DEFAULT.state_from_board(&board).evaluate(side_to_move);
+            let doubled_cp = doubled.state_from_board(&board).evaluate(side_to_move);
+            // Only the rounding of the division differs.
+            assert!((doubled_cp - 2 * cp).abs() <= 1, "{} {} {}", sample.fen, cp, doubled_cp);
+        }
+    }
+
+    #[test]
+    fn state_from_board_matches_manual_build() {
+        for sample in sample_positions(4, 50, 40) {
+            let board: Board = sample.fen.parse().unwrap();
+            let mut manual = Nnue::DEFAULT.new_state();
+            for &color in &Color::ALL {
+                for &piece in &Piece::ALL {
+                    for square in board.colors(color) & board.pieces(piece) {
+                        manual.add(color, piece, square);
+                    }
+                }
+            }
+            let state = Nnue::DEFAULT.state_from_board(&board);
+            assert_eq!(state.accumulator(), manual.accumulator(), "{}", sample.fen);
+        }
+    }
+
+    #[test]
+    fn raw_to_cp_ratio() {
+        let board: Board = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3".parse().unwrap();
+        let state = Nnue::DEFAULT.state_from_board(&board);
+        for &color in &Color::ALL {
+            let raw = state.evaluate_raw(color);
+            assert_eq!(state.evaluate_cp(color), raw * 115 / (64 * 127));
+            assert_eq!(state.evaluate(color), state.evaluate_cp(color));
+        }
+    }
+
+    #[test]
+    fn split_inputs_matches_nested_layout() {
+        let mut rng = crate::tune::Rng::new(5);
+        let random = (0..20).map(|_| {
+            let mut accumulator = [[0; FT_OUT]; Color::NUM];
+            accumulator.iter_mut().flatten().for_each(|v| *v = rng.next() as i16 / 64);
+            accumulator
+        });
+        let positions = sample_positions(5, 20, 40).into_iter()
+            .map(|sample| *Nnue::DEFAULT.state_from_board(&sample.fen.parse().unwrap()).accumulator());
+        for accumulator in random.chain(positions) {
+            let mut inputs = [0; FT_OUT * Color::NUM];
+            let (us, them) = split_inputs(&mut inputs);
+            accumulator[0].clipped_relu(0, ACTIVATION_RANGE, us);
+            accumulator[1].clipped_relu(0, ACTIVATION_RANGE, them);
+            // The layout before split_inputs, one array per perspective.
+            let mut nested = [[0; FT_OUT]; Color::NUM];
+            accumulator[0].clipped_relu(0, ACTIVATION_RANGE, &mut nested[0]);
+            accumulator[1].clipped_relu(0, ACTIVATION_RANGE, &mut nested[1]);
+            assert_eq!(inputs.to_vec(), nested.concat());
+        }
+    }
+
+    ///Play each move on `fen` incrementally and compare against a full rebuild,
+    ///then check that unplaying restores the original accumulator.
+    fn assert_incremental_matches_rebuild(fen: &str, moves: &[&str]) {
+        assert_incremental_matches_rebuild_with::<Standard, FEATURES>(&Nnue::DEFAULT, fen, moves);
+    }
+
+    fn assert_incremental_matches_rebuild_with<F: FeatureSet<INPUTS>, const INPUTS: usize>(
+        model: &Nnue<INPUTS>,
+        fen: &str,
+        moves: &[&str]
+    ) {
+        let board: Board = fen.parse().unwrap();
+        for &mv in moves {
+            let mv = mv.parse().unwrap();
+            let mut state = model.state_from_board_with::<F>(&board);
+            let before = *state.accumulator();
+            state.play(&board, mv);
+            let mut child = board.clone();
+            child.play(mv);
+            assert!(state.verify(&child), "{} {}", fen, mv);
+            state.unplay();
+            assert_eq!(*state.accumulator(), before, "{} {}", fen, mv);
+        }
+    }
+
+    #[test]
+    fn promotions_match_rebuild() {
+        assert_incremental_matches_rebuild("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", &[
+            "a7a8q", "a7a8r", "a7a8b", "a7a8n",
+            "a7b8q", "a7b8r", "a7b8b", "a7b8n"
+        ]);
+        assert_incremental_matches_rebuild("4k3/8/8/8/8/8/p7/1R2K3 b - - 0 1", &[
+            "a2a1q", "a2a1r", "a2a1b", "a2a1n",
+            "a2b1q", "a2b1r", "a2b1b", "a2b1n"
+        ]);
+    }
+
+    #[test]
+    fn en_passant_matches_rebuild() {
+        assert_incremental_matches_rebuild("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2", &["e5d6"]);
+        assert_incremental_matches_rebuild("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1", &["d4e3"]);
+    }
+
+    ///A HalfKA network with random feature transformer weights, since there is no trained one.
+    pub(super) fn random_half_ka(seed: u64) -> Box<Nnue<HALF_KA_FEATURES>> {
+        let mut half_ka = Nnue::<HALF_KA_FEATURES>::new_zeroed();
+        let mut rng = crate::tune::Rng::new(seed);
+        half_ka.ft.weights.iter_mut().flatten().for_each(|w| *w = rng.next() as i16 / 256);
+        half_ka
+    }
+
+    fn assert_refresh_both_matches_refresh<F: FeatureSet<INPUTS>, const INPUTS: usize>(model: &Nnue<INPUTS>) {
+        let mut both = model.new_state_with::<F>(&Board::default());
+        let mut separate = model.new_state_with::<F>(&Board::default());
+        for sample in sample_positions(8, 50, 60) {
+            let board = sample.fen.parse().unwrap();
+            both.refresh_both(&board);
+            separate.refresh(Color::White, &board);
+            separate.refresh(Color::Black, &board);
+            assert_eq!(both.accumulator(), separate.accumulator(), "{}", sample.fen);
+            assert_eq!(both.kings, separate.kings, "{}", sample.fen);
+        }
+    }
+
+    #[test]
+    fn refresh_both_matches_refresh() {
+        assert_refresh_both_matches_refresh::<Standard, FEATURES>(&Nnue::DEFAULT);
+        assert_refresh_both_matches_refresh::<HalfKa, HALF_KA_FEATURES>(&random_half_ka(8));
+    }
+
+    const CASTLING_FENS: [(&str, &[&str]); 2] = [
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", &["e1h1", "e1a1"]),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", &["e8h8", "e8a8"])
+    ];
+
+    #[test]
+    fn castling_matches_rebuild() {
+        for (fen, moves) in CASTLING_FENS {
+            assert_incremental_matches_rebuild(fen, moves);
+        }
+    }
+
+    #[test]
+    fn half_ka_king_moves_match_rebuild() {
+        let half_ka = random_half_ka(9);
+        for (fen, moves) in CASTLING_FENS {
+            assert_incremental_matches_rebuild_with::<HalfKa, HALF_KA_FEATURES>(&half_ka, fen, moves);
+        }
+        // Quiet king moves within and across the mirrored half, a king capture, and a non-king move.
+        let fen = "4k3/8/8/8/8/8/5r2/4K3 w - - 0 1";
+        let moves = ["e1d1", "e1f2"];
+        assert_incremental_matches_rebuild_with::<HalfKa, HALF_KA_FEATURES>(&half_ka, fen, &moves);
+        let fen = "4k3/8/8/8/8/8/5r2/3K4 w - - 0 1";
+        let moves = ["d1e1", "d1c1"];
+        assert_incremental_matches_rebuild_with::<HalfKa, HALF_KA_FEATURES>(&half_ka, fen, &moves);
+        let fen = "4k3/8/8/8/8/8/5r2/3K4 b - - 0 1";
+        let moves = ["e8d8", "e8f7", "f2f1"];
+        assert_incremental_matches_rebuild_with::<HalfKa, HALF_KA_FEATURES>(&half_ka, fen, &moves);
+    }
+
+    #[test]
+    fn evaluate_batch_matches_serial() {
+        let boards: Vec<Board> = sample_positions(11, 37, 40).into_iter()
+            .map(|sample| sample.fen.parse().unwrap())
+            .collect();
+        let serial: Vec<_> = boards.iter()
+            .map(|board| Nnue::DEFAULT.state_from_board(board).evaluate(board.side_to_move()))
+            .collect();
+        assert_eq!(Nnue::DEFAULT.evaluate_batch(&boards), serial);
+        for threads in [1, 2, 4, 64] {
+            // Includes no boards and fewer boards than threads.
+            for len in [0, 1, 3, boards.len()] {
+                let batch = Nnue::DEFAULT.evaluate_batch_with_threads(&boards[..len], threads);
+                assert_eq!(batch, serial[..len], "{} threads, {} boards", threads, len);
+            }
+        }
+    }
+
+    #[test]
+    fn evaluate_fen_rejects_bad_fens() {
+        assert!(Nnue::DEFAULT.evaluate_fen("not a fen").is_err());
+        assert!(Nnue::DEFAULT.evaluate_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1").is_err());
+        // The side not to move is in check.
+        assert!(Nnue::DEFAULT.evaluate_fen("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1").is_err());
+        let board = Board::default();
+        let expected = Nnue::DEFAULT.state_from_board(&board).evaluate(board.side_to_move());
+        assert_eq!(Nnue::DEFAULT.evaluate_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap(), expected);
     }
 }
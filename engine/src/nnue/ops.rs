@@ -19,49 +19,347 @@ macro_rules! vec_op_fallbacks {
     };
 }
 
+///Two additions done in the same pass, so both accumulators are updated together.
+pub trait VecAddPair {
+    fn vec_add_pair(&mut self, other: &Self, second: &mut Self, second_other: &Self);
+}
+
+macro_rules! vec_add_pair_fallbacks {
+    ($($type:ty),*) => {
+        $(impl<const SIZE: usize> VecAddPair for [$type; SIZE] {
+            fn vec_add_pair(&mut self, other: &Self, second: &mut Self, second_other: &Self) {
+                for i in 0..SIZE {
+                    self[i] = self[i].wrapping_add(other[i]);
+                    second[i] = second[i].wrapping_add(second_other[i]);
+                }
+            }
+        })*
+    };
+}
+
 macro_rules! vec_add_sub_fallbacks {
     ($($type:ty),*) => {
         vec_op_fallbacks!(VecAdd, vec_add, wrapping_add $(, $type)*);
         vec_op_fallbacks!(VecSub, vec_sub, wrapping_sub $(, $type)*);
+        vec_add_pair_fallbacks!($($type),*);
     };
 }
 
-vec_add_sub_fallbacks!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128);
+vec_add_sub_fallbacks!(u8, i8, u16, u32, i32, u64, i64, u128, i128);
 
-pub trait Dot<Output> {
-    fn dot(&self, other: &Self) -> Output;
+macro_rules! vec_op_i16 {
+    ($trait:ident, $fn:ident, $simd_fn:ident) => {
+        impl<const SIZE: usize> $trait for [i16; SIZE] {
+            fn $fn(&mut self, other: &Self) {
+                #[cfg(target_arch = "x86_64")]
+                if cfg!(target_feature = "avx2") || is_x86_feature_detected!("avx2") {
+                    // SAFETY: AVX2 support was checked above.
+                    unsafe {
+                        avx2::$simd_fn(self, other);
+                    }
+                    return;
+                }
+                scalar::$simd_fn(self, other);
+            }
+        }
+    };
 }
 
-macro_rules! dot_product_fallbacks {
-    ($($type:ty => $out:ty),*) => {
-        $(impl<const SIZE: usize> Dot<$out> for [$type; SIZE] {
-            fn dot(&self, other: &Self) -> $out {
-                self.iter().zip(other).map(|(&l, &r)| l as $out * r as $out).sum()
+vec_op_i16!(VecAdd, vec_add, vec_add_i16);
+vec_op_i16!(VecSub, vec_sub, vec_sub_i16);
+
+impl<const SIZE: usize> VecAddPair for [i16; SIZE] {
+    fn vec_add_pair(&mut self, other: &Self, second: &mut Self, second_other: &Self) {
+        #[cfg(target_arch = "x86_64")]
+        if cfg!(target_feature = "avx2") || is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 support was checked above.
+            unsafe {
+                avx2::vec_add_pair_i16(self, other, second, second_other);
             }
-        })*
-    };
+            return;
+        }
+        scalar::vec_add_pair_i16(self, other, second, second_other);
+    }
+}
+
+pub trait Dot<Output> {
+    fn dot(&self, other: &Self) -> Output;
 }
 
-dot_product_fallbacks! {
-    i8 => i32
+impl<const SIZE: usize> Dot<i32> for [i8; SIZE] {
+    fn dot(&self, other: &Self) -> i32 {
+        #[cfg(target_arch = "x86_64")]
+        if cfg!(target_feature = "avx2") || is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 support was checked above.
+            return unsafe { avx2::dot_i8(self, other) };
+        }
+        scalar::dot_i8(self, other)
+    }
 }
 
 pub trait ClippedRelu<O, const SIZE: usize> {
     fn clipped_relu(&self, min: O, max: O, out: &mut [O; SIZE]);
 }
 
-macro_rules! clipped_relu_fallbacks {
-    ($($type:ty => $out:ty),*) => {
-        $(impl<const SIZE: usize> ClippedRelu<$out, SIZE> for [$type; SIZE] {
-            fn clipped_relu(&self, min: $out, max: $out, out: &mut [$out; SIZE]) {
-                for (&v, o) in self.iter().zip(out) {
-                    *o = v.clamp(min as $type, max as $type) as $out;
+impl<const SIZE: usize> ClippedRelu<i8, SIZE> for [i16; SIZE] {
+    fn clipped_relu(&self, min: i8, max: i8, out: &mut [i8; SIZE]) {
+        #[cfg(target_arch = "x86_64")]
+        if cfg!(target_feature = "avx2") || is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 support was checked above.
+            unsafe {
+                avx2::clipped_relu_i16(self, min, max, out);
+            }
+            return;
+        }
+        scalar::clipped_relu_i16(self, min, max, out);
+    }
+}
+
+///The portable implementations, used when no SIMD path is available.
+pub(super) mod scalar {
+    macro_rules! scalar_vec_op_i16 {
+        ($fn:ident, $op:tt) => {
+            pub fn $fn(lhs: &mut [i16], rhs: &[i16]) {
+                for (l, r) in lhs.iter_mut().zip(rhs) {
+                    *l = l.$op(*r);
                 }
             }
-        })*
-    };
+        };
+    }
+
+    scalar_vec_op_i16!(vec_add_i16, wrapping_add);
+    scalar_vec_op_i16!(vec_sub_i16, wrapping_sub);
+
+    pub fn vec_add_pair_i16(lhs: &mut [i16], rhs: &[i16], second_lhs: &mut [i16], second_rhs: &[i16]) {
+        let len = lhs.len().min(rhs.len()).min(second_lhs.len()).min(second_rhs.len());
+        for i in 0..len {
+            lhs[i] = lhs[i].wrapping_add(rhs[i]);
+            second_lhs[i] = second_lhs[i].wrapping_add(second_rhs[i]);
+        }
+    }
+
+    pub fn dot_i8(lhs: &[i8], rhs: &[i8]) -> i32 {
+        lhs.iter().zip(rhs).map(|(&l, &r)| l as i32 * r as i32).sum()
+    }
+
+    pub fn clipped_relu_i16(input: &[i16], min: i8, max: i8, out: &mut [i8]) {
+        for (&v, o) in input.iter().zip(out) {
+            *o = v.clamp(min as i16, max as i16) as i8;
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    const LANES: usize = 16;
+
+    macro_rules! avx2_vec_op_i16 {
+        ($fn:ident, $intrinsic:ident, $op:tt) => {
+            #[target_feature(enable = "avx2")]
+            pub unsafe fn $fn(lhs: &mut [i16], rhs: &[i16]) {
+                let len = lhs.len().min(rhs.len());
+                let chunks = len / LANES;
+                for i in 0..chunks {
+                    let l = lhs.as_mut_ptr().add(i * LANES) as *mut __m256i;
+                    let r = rhs.as_ptr().add(i * LANES) as *const __m256i;
+                    _mm256_storeu_si256(l, $intrinsic(_mm256_loadu_si256(l), _mm256_loadu_si256(r)));
+                }
+                for i in (chunks * LANES)..len {
+                    lhs[i] = lhs[i].$op(rhs[i]);
+                }
+            }
+        };
+    }
+
+    avx2_vec_op_i16!(vec_add_i16, _mm256_add_epi16, wrapping_add);
+    avx2_vec_op_i16!(vec_sub_i16, _mm256_sub_epi16, wrapping_sub);
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn vec_add_pair_i16(lhs: &mut [i16], rhs: &[i16], second_lhs: &mut [i16], second_rhs: &[i16]) {
+        let len = lhs.len().min(rhs.len()).min(second_lhs.len()).min(second_rhs.len());
+        let chunks = len / LANES;
+        for i in 0..chunks {
+            let l = lhs.as_mut_ptr().add(i * LANES) as *mut __m256i;
+            let r = rhs.as_ptr().add(i * LANES) as *const __m256i;
+            let second_l = second_lhs.as_mut_ptr().add(i * LANES) as *mut __m256i;
+            let second_r = second_rhs.as_ptr().add(i * LANES) as *const __m256i;
+            _mm256_storeu_si256(l, _mm256_add_epi16(_mm256_loadu_si256(l), _mm256_loadu_si256(r)));
+            _mm256_storeu_si256(second_l, _mm256_add_epi16(
+                _mm256_loadu_si256(second_l),
+                _mm256_loadu_si256(second_r)
+            ));
+        }
+        for i in (chunks * LANES)..len {
+            lhs[i] = lhs[i].wrapping_add(rhs[i]);
+            second_lhs[i] = second_lhs[i].wrapping_add(second_rhs[i]);
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn dot_i8(lhs: &[i8], rhs: &[i8]) -> i32 {
+        let len = lhs.len().min(rhs.len());
+        let chunks = len / LANES;
+        let mut sum = _mm256_setzero_si256();
+        for i in 0..chunks {
+            let l = _mm_loadu_si128(lhs.as_ptr().add(i * LANES) as *const __m128i);
+            let r = _mm_loadu_si128(rhs.as_ptr().add(i * LANES) as *const __m128i);
+            // Sign extend both sides so the products are exact for any i8 inputs.
+            let products = _mm256_madd_epi16(_mm256_cvtepi8_epi16(l), _mm256_cvtepi8_epi16(r));
+            sum = _mm256_add_epi32(sum, products);
+        }
+        let sum = _mm_add_epi32(_mm256_castsi256_si128(sum), _mm256_extracti128_si256::<1>(sum));
+        let sum = _mm_add_epi32(sum, _mm_shuffle_epi32::<0b01_00_11_10>(sum));
+        let sum = _mm_add_epi32(sum, _mm_shuffle_epi32::<0b10_11_00_01>(sum));
+        let mut total = _mm_cvtsi128_si32(sum);
+        for i in (chunks * LANES)..len {
+            total += lhs[i] as i32 * rhs[i] as i32;
+        }
+        total
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn clipped_relu_i16(input: &[i16], min: i8, max: i8, out: &mut [i8]) {
+        let len = input.len().min(out.len());
+        let chunks = len / (LANES * 2);
+        let min_vec = _mm256_set1_epi16(min as i16);
+        let max_vec = _mm256_set1_epi16(max as i16);
+        for i in 0..chunks {
+            let chunk = input.as_ptr().add(i * LANES * 2) as *const __m256i;
+            let low = _mm256_loadu_si256(chunk);
+            let high = _mm256_loadu_si256(chunk.add(1));
+            let low = _mm256_min_epi16(_mm256_max_epi16(low, min_vec), max_vec);
+            let high = _mm256_min_epi16(_mm256_max_epi16(high, min_vec), max_vec);
+            // Packing works within 128 bit lanes, so the 64 bit blocks are out of order after.
+            let packed = _mm256_packs_epi16(low, high);
+            let packed = _mm256_permute4x64_epi64::<0b11_01_10_00>(packed);
+            _mm256_storeu_si256(out.as_mut_ptr().add(i * LANES * 2) as *mut __m256i, packed);
+        }
+        for i in (chunks * LANES * 2)..len {
+            out[i] = input[i].clamp(min as i16, max as i16) as i8;
+        }
+    }
 }
 
-clipped_relu_fallbacks! {
-    i16 => i8
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tune::Rng;
+
+    const LENGTHS: [usize; 11] = [0, 1, 15, 16, 17, 31, 32, 33, 47, 64, 100];
+
+    fn random_i16s(rng: &mut Rng, len: usize) -> Vec<i16> {
+        (0..len).map(|_| rng.next() as i16).collect()
+    }
+
+    fn random_i8s(rng: &mut Rng, len: usize) -> Vec<i8> {
+        (0..len).map(|_| rng.next() as i8).collect()
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn avx2_add_sub_matches_scalar() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+        let mut rng = Rng::new(0);
+        for len in LENGTHS {
+            let mut simd = random_i16s(&mut rng, len);
+            let mut reference = simd.clone();
+            for _ in 0..100 {
+                let other = random_i16s(&mut rng, len);
+                if rng.below(2) == 0 {
+                    // SAFETY: AVX2 support was checked above.
+                    unsafe { avx2::vec_add_i16(&mut simd, &other) };
+                    scalar::vec_add_i16(&mut reference, &other);
+                } else {
+                    // SAFETY: AVX2 support was checked above.
+                    unsafe { avx2::vec_sub_i16(&mut simd, &other) };
+                    scalar::vec_sub_i16(&mut reference, &other);
+                }
+                assert_eq!(simd, reference, "length {}", len);
+            }
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn avx2_add_pair_matches_scalar() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+        let mut rng = Rng::new(0);
+        for len in LENGTHS {
+            let mut simd = [random_i16s(&mut rng, len), random_i16s(&mut rng, len)];
+            let mut reference = simd.clone();
+            for _ in 0..100 {
+                let others = [random_i16s(&mut rng, len), random_i16s(&mut rng, len)];
+                let [first, second] = &mut simd;
+                // SAFETY: AVX2 support was checked above.
+                unsafe { avx2::vec_add_pair_i16(first, &others[0], second, &others[1]) };
+                let [first, second] = &mut reference;
+                scalar::vec_add_pair_i16(first, &others[0], second, &others[1]);
+                assert_eq!(simd, reference, "length {}", len);
+            }
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn avx2_dot_matches_scalar() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+        let mut rng = Rng::new(0);
+        for len in LENGTHS {
+            for _ in 0..100 {
+                let lhs = random_i8s(&mut rng, len);
+                let rhs = random_i8s(&mut rng, len);
+                // SAFETY: AVX2 support was checked above.
+                let simd = unsafe { avx2::dot_i8(&lhs, &rhs) };
+                assert_eq!(simd, scalar::dot_i8(&lhs, &rhs), "length {}", len);
+            }
+        }
+        // The extremes, where sign extension matters.
+        let lhs = [i8::MIN; 64];
+        let rhs = [i8::MIN; 64];
+        // SAFETY: AVX2 support was checked above.
+        assert_eq!(unsafe { avx2::dot_i8(&lhs, &rhs) }, 64 * 128 * 128);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn avx2_clipped_relu_matches_scalar() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+        let mut rng = Rng::new(0);
+        for len in LENGTHS {
+            for _ in 0..100 {
+                let input = random_i16s(&mut rng, len);
+                let mut simd = vec![0; len];
+                let mut reference = vec![0; len];
+                // SAFETY: AVX2 support was checked above.
+                unsafe { avx2::clipped_relu_i16(&input, 0, 127, &mut simd) };
+                scalar::clipped_relu_i16(&input, 0, 127, &mut reference);
+                assert_eq!(simd, reference, "length {}", len);
+            }
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn avx2_clipped_relu_keeps_order() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+        // Every output is distinct, so any lane reordered by the pack is caught.
+        let input: Vec<i16> = (0..64).collect();
+        let mut out = [0; 64];
+        // SAFETY: AVX2 support was checked above.
+        unsafe { avx2::clipped_relu_i16(&input, 0, 127, &mut out) };
+        assert!(out.iter().enumerate().all(|(i, &o)| o as usize == i), "{:?}", out);
+    }
 }
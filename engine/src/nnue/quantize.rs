@@ -0,0 +1,132 @@
+use super::*;
+
+///A network's weights before quantization, in the same layout as [`Nnue`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloatNnue<const INPUTS: usize = FEATURES> {
+    pub ft_weights: [[f32; FT_OUT]; INPUTS],
+    pub ft_biases: [f32; FT_OUT],
+    pub l1_weights: [[f32; FT_OUT * Color::NUM]; L1_OUT],
+    pub l1_biases: [f32; L1_OUT]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErrorStats {
+    ///The largest absolute difference between a float value and its dequantized value.
+    pub max: f32,
+    ///The mean absolute difference between the float values and their dequantized values.
+    pub mean: f32,
+    ///How many values fall outside the range of the quantized type once scaled.
+    pub out_of_range: usize
+}
+
+impl ErrorStats {
+    fn new<Q: Into<i64>>(
+        values: impl IntoIterator<Item=(f32, Q)>,
+        scale: f32,
+        range: (Q, Q)
+    ) -> Self {
+        let (min, max) = (range.0.into() as f32, range.1.into() as f32);
+        let mut stats = Self {
+            max: 0.0,
+            mean: 0.0,
+            out_of_range: 0
+        };
+        let mut count = 0;
+        for (value, quantized) in values {
+            let error = (value - quantized.into() as f32 / scale).abs();
+            stats.max = stats.max.max(error);
+            stats.mean += error;
+            let scaled = (value * scale).round();
+            if scaled < min || scaled > max {
+                stats.out_of_range += 1;
+            }
+            count += 1;
+        }
+        stats.mean /= count as f32;
+        stats
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuantizationError {
+    pub ft_weights: ErrorStats,
+    pub ft_biases: ErrorStats,
+    pub l1_weights: ErrorStats,
+    pub l1_biases: ErrorStats
+}
+
+impl<const INPUTS: usize> Nnue<INPUTS> {
+    ///Compare this network against the float weights it was quantized from.
+    ///Feature transformer values are scaled by the activation range,
+    ///`l1` weights by the weight scale, and `l1` biases by both.
+    pub fn quantization_error(&self, float: &FloatNnue<INPUTS>) -> QuantizationError {
+        let activation_range = self.activation_range as f32;
+        let weight_scale = self.weight_scale as f32;
+        QuantizationError {
+            ft_weights: ErrorStats::new(
+                float.ft_weights.iter().flatten().copied().zip(self.ft.weights.iter().flatten().copied()),
+                activation_range,
+                (i16::MIN, i16::MAX)
+            ),
+            ft_biases: ErrorStats::new(
+                float.ft_biases.iter().copied().zip(self.ft.biases),
+                activation_range,
+                (i16::MIN, i16::MAX)
+            ),
+            l1_weights: ErrorStats::new(
+                float.l1_weights.iter().flatten().copied().zip(self.l1.weights.iter().flatten().copied()),
+                weight_scale,
+                (i8::MIN, i8::MAX)
+            ),
+            l1_biases: ErrorStats::new(
+                float.l1_biases.iter().copied().zip(self.l1.biases),
+                weight_scale * activation_range,
+                (i32::MIN, i32::MAX)
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dequantize(nnue: &Nnue) -> FloatNnue {
+        let activation_range = nnue.activation_range as f32;
+        let weight_scale = nnue.weight_scale as f32;
+        FloatNnue {
+            ft_weights: nnue.ft.weights.map(|weights| weights.map(|w| w as f32 / activation_range)),
+            ft_biases: nnue.ft.biases.map(|b| b as f32 / activation_range),
+            l1_weights: nnue.l1.weights.map(|weights| weights.map(|w| w as f32 / weight_scale)),
+            l1_biases: nnue.l1.biases.map(|b| b as f32 / (weight_scale * activation_range))
+        }
+    }
+
+    #[test]
+    fn exact_weights_have_no_error() {
+        let error = Nnue::DEFAULT.quantization_error(&dequantize(&Nnue::DEFAULT));
+        for stats in [error.ft_weights, error.ft_biases, error.l1_weights, error.l1_biases] {
+            assert_eq!(stats, ErrorStats { max: 0.0, mean: 0.0, out_of_range: 0 });
+        }
+    }
+
+    #[test]
+    fn perturbed_weights_are_reported() {
+        let mut float = dequantize(&Nnue::DEFAULT);
+        let activation_range = Nnue::DEFAULT.activation_range as f32;
+        let weight_scale = Nnue::DEFAULT.weight_scale as f32;
+        float.ft_weights[10][3] += 0.25 / activation_range;
+        // Far outside i8 once scaled by the weight scale.
+        float.l1_weights[0][5] = 1000.0 / weight_scale;
+        let error = Nnue::DEFAULT.quantization_error(&float);
+
+        let expected_max = 0.25 / activation_range;
+        assert!((error.ft_weights.max - expected_max).abs() < 1e-6);
+        let expected_mean = expected_max / (FEATURES * FT_OUT) as f32;
+        assert!((error.ft_weights.mean - expected_mean).abs() < 1e-9);
+        assert_eq!(error.ft_weights.out_of_range, 0);
+        assert_eq!(error.l1_weights.out_of_range, 1);
+        assert_eq!(error.ft_biases.max, 0.0);
+        assert_eq!(error.l1_biases.max, 0.0);
+    }
+}
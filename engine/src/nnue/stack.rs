@@ -0,0 +1,82 @@
+use cozy_chess::*;
+
+use super::*;
+
+///A stack of accumulators for make/unmake style tree traversal.
+///The top of the stack is the state for the current position.
+#[derive(Debug, Clone)]
+pub struct AccumulatorStack<'m, F = Standard, const INPUTS: usize = FEATURES> {
+    state: NnueState<'m, F, INPUTS>
+}
+
+impl<'m, F: FeatureSet<INPUTS>, const INPUTS: usize> AccumulatorStack<'m, F, INPUTS> {
+    pub fn new(model: &'m Nnue<INPUTS>, board: &Board) -> Self {
+        Self {
+            state: model.state_from_board_with(board)
+        }
+    }
+
+    ///Clear the stack and rebuild the accumulator from `board`.
+    pub fn reset(&mut self, board: &Board) {
+        self.state.history.clear();
+        self.state.refresh_both(board);
+    }
+
+    ///Push the state after playing `mv` on `board`.
+    pub fn push_move(&mut self, board: &Board, mv: Move) {
+        self.state.play(board, mv);
+    }
+
+    ///Pop the top state, restoring the parent state exactly.
+    pub fn pop(&mut self) {
+        self.state.unplay();
+    }
+
+    ///The number of moves pushed since the last reset.
+    pub fn len(&self) -> usize {
+        self.state.history.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.history.is_empty()
+    }
+
+    pub fn top(&self) -> &NnueState<'m, F, INPUTS> {
+        &self.state
+    }
+
+    pub fn evaluate(&self, side_to_move: Color) -> i32 {
+        self.state.evaluate(side_to_move)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pop_restores_parent_and_reset_rebuilds() {
+        let root: Board = "r3k2r/8/8/8/4p3/8/3P4/R3K2R w KQkq - 0 1".parse().unwrap();
+        let mut stack = AccumulatorStack::<Standard>::new(&Nnue::DEFAULT, &root);
+        let root_accumulator = *stack.top().accumulator();
+        let mut board = root.clone();
+        // A double push, en passant, castling and a capture.
+        for (ply, mv) in ["d2d4", "e4d3", "e1h1", "a8a1"].into_iter().enumerate() {
+            let mv = mv.parse().unwrap();
+            stack.push_move(&board, mv);
+            board.play(mv);
+            assert_eq!(stack.len(), ply + 1);
+            assert!(stack.top().verify(&board), "{}", mv);
+        }
+        while !stack.is_empty() {
+            stack.pop();
+        }
+        assert_eq!(*stack.top().accumulator(), root_accumulator);
+        assert!(stack.top().verify(&root));
+
+        stack.push_move(&root, "d2d4".parse().unwrap());
+        stack.reset(&board);
+        assert!(stack.is_empty());
+        assert!(stack.top().verify(&board));
+    }
+}
@@ -0,0 +1,244 @@
+use std::fmt::{Display, Formatter};
+
+use cozy_chess::*;
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValueStats {
+    pub min: i32,
+    pub max: i32,
+    pub mean: f32
+}
+
+impl ValueStats {
+    fn new(values: impl IntoIterator<Item=i32>) -> Self {
+        let mut min = i32::MAX;
+        let mut max = i32::MIN;
+        let mut sum = 0i64;
+        let mut count = 0;
+        for value in values {
+            min = min.min(value);
+            max = max.max(value);
+            sum += value as i64;
+            count += 1;
+        }
+        Self {
+            min,
+            max,
+            mean: sum as f32 / count as f32
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NnueStats {
+    pub ft_weights: ValueStats,
+    pub ft_biases: ValueStats,
+    pub l1_weights: ValueStats,
+    pub l1_biases: ValueStats
+}
+
+///How many feature transformer outputs for a perspective fall in each region of the clipped ReLU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ActivationHistogram {
+    ///Outputs clipped to zero.
+    pub zero: usize,
+    ///Outputs between zero and the activation range.
+    pub linear: usize,
+    ///Outputs clipped to the activation range.
+    pub saturated: usize
+}
+
+impl<const INPUTS: usize> Nnue<INPUTS> {
+    pub fn stats(&self) -> NnueStats {
+        NnueStats {
+            ft_weights: ValueStats::new(self.ft.weights.iter().flatten().map(|&w| w as i32)),
+            ft_biases: ValueStats::new(self.ft.biases.iter().map(|&b| b as i32)),
+            l1_weights: ValueStats::new(self.l1.weights.iter().flatten().map(|&w| w as i32)),
+            l1_biases: ValueStats::new(self.l1.biases.iter().copied())
+        }
+    }
+}
+
+///Feature transformer outputs that never vary over a set of positions.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PruneReport {
+    ///Outputs that were clipped to zero in every position, so they never contribute.
+    pub dead: Vec<usize>,
+    ///Outputs that were clipped to the activation range in every position,
+    ///so they only contribute a constant.
+    pub saturated: Vec<usize>
+}
+
+impl Nnue {
+    ///Find the feature transformer outputs that never vary over `boards`.
+    pub fn prune_report(&self, boards: &[Board]) -> PruneReport {
+        let mut dead = [true; FT_OUT];
+        let mut saturated = [true; FT_OUT];
+        for board in boards {
+            let state = self.state_from_board(board);
+            for accumulator in state.accumulator.iter() {
+                for (i, &output) in accumulator.iter().enumerate() {
+                    dead[i] &= output <= 0;
+                    saturated[i] &= output >= self.activation_range as i16;
+                }
+            }
+        }
+        let outputs = |flags: [bool; FT_OUT]| (0..FT_OUT).filter(|&i| flags[i]).collect();
+        PruneReport {
+            dead: outputs(dead),
+            saturated: outputs(saturated)
+        }
+    }
+}
+
+impl<const INPUTS: usize> Nnue<INPUTS> {
+    ///Zero the weights of the given feature transformer outputs and their `l1` inputs.
+    ///The outputs can't actually be removed since the layer sizes are compile time constants.
+    ///Shrinking the network needs a runtime sized variant of the layers and of the binary format.
+    ///Zeroing the dead outputs of a [`PruneReport`] doesn't change the evaluation of its positions.
+    ///# Panics
+    ///Panics if any of `outputs` is not a feature transformer output index.
+    pub fn zero_ft_outputs(&mut self, outputs: &[usize]) {
+        for &output in outputs {
+            for weights in &mut self.ft.weights {
+                weights[output] = 0;
+            }
+            self.ft.biases[output] = 0;
+            for weights in &mut self.l1.weights {
+                weights[output] = 0;
+                weights[FT_OUT + output] = 0;
+            }
+        }
+    }
+}
+
+impl<F: FeatureSet<INPUTS>, const INPUTS: usize> NnueState<'_, F, INPUTS> {
+    ///Count how many feature transformer outputs clip for each perspective.
+    pub fn activation_histogram(&self) -> [ActivationHistogram; Color::NUM] {
+        let range = self.model.activation_range as i16;
+        let mut histograms = [ActivationHistogram::default(); Color::NUM];
+        for (accumulator, histogram) in self.accumulator.iter().zip(&mut histograms) {
+            for &output in accumulator {
+                match output {
+                    o if o <= 0 => histogram.zero += 1,
+                    o if o >= range => histogram.saturated += 1,
+                    _ => histogram.linear += 1
+                }
+            }
+        }
+        histograms
+    }
+
+    ///Like [`NnueState::evaluate`], but with the given feature transformer outputs
+    ///zeroed for both perspectives, to measure how much they contribute.
+    ///Dropping every output leaves only the `l1` bias.
+    ///# Panics
+    ///Panics if any of `dropped` is not a feature transformer output index.
+    pub fn evaluate_with_dropout(&self, side_to_move: Color, dropped: &[usize]) -> i32 {
+        let mut inputs = [0; FT_OUT * Color::NUM];
+        let (us, them) = split_inputs(&mut inputs);
+        self.accumulator[side_to_move as usize]
+            .clipped_relu(0, self.model.activation_range, us);
+        self.accumulator[(!side_to_move) as usize]
+            .clipped_relu(0, self.model.activation_range, them);
+        for &output in dropped {
+            us[output] = 0;
+            them[output] = 0;
+        }
+        self.model.raw_to_cp(self.forward(&inputs))
+    }
+}
+
+impl<F, const INPUTS: usize> Display for NnueState<'_, F, INPUTS> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for &perspective in &Color::ALL {
+            write!(f, "{:<6}", format!("{:?}:", perspective))?;
+            for output in &self.accumulator[perspective as usize] {
+                write!(f, " {:>5}", output)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_outputs() -> Vec<usize> {
+        (0..FT_OUT).collect()
+    }
+
+    #[test]
+    fn dropping_every_output_leaves_only_the_bias() {
+        let bias = Nnue::DEFAULT.raw_to_cp(Nnue::DEFAULT.l1.biases[0]);
+        for sample in crate::tune::sample_positions(7, 20, 40) {
+            let board: Board = sample.fen.parse().unwrap();
+            let state = Nnue::DEFAULT.state_from_board(&board);
+            let side_to_move = board.side_to_move();
+            assert_eq!(state.evaluate_with_dropout(side_to_move, &all_outputs()), bias);
+            assert_eq!(state.evaluate_with_dropout(side_to_move, &[]), state.evaluate(side_to_move));
+        }
+    }
+
+    #[test]
+    fn zeroing_every_output_leaves_only_the_bias() {
+        let mut nnue = Nnue::DEFAULT;
+        nnue.zero_ft_outputs(&all_outputs());
+        let board = Board::default();
+        let bias = nnue.raw_to_cp(nnue.l1.biases[0]);
+        assert_eq!(nnue.state_from_board(&board).evaluate(board.side_to_move()), bias);
+    }
+
+    #[test]
+    #[should_panic]
+    fn dropping_an_invalid_output_panics() {
+        let board = Board::default();
+        Nnue::DEFAULT.state_from_board(&board).evaluate_with_dropout(Color::White, &[FT_OUT]);
+    }
+
+    #[test]
+    fn histogram_counts_every_output() {
+        let range = Nnue::DEFAULT.activation_range as i16;
+        for sample in crate::tune::sample_positions(13, 20, 40) {
+            let board: Board = sample.fen.parse().unwrap();
+            let state = Nnue::DEFAULT.state_from_board(&board);
+            let histograms = state.activation_histogram();
+            for (accumulator, histogram) in state.accumulator.iter().zip(&histograms) {
+                assert_eq!(histogram.zero + histogram.linear + histogram.saturated, FT_OUT);
+                let zero = accumulator.iter().filter(|&&o| o <= 0).count();
+                let saturated = accumulator.iter().filter(|&&o| o >= range).count();
+                assert_eq!(histogram.zero, zero);
+                assert_eq!(histogram.saturated, saturated);
+            }
+        }
+    }
+
+    #[test]
+    fn stats_match_weights() {
+        let stats = Nnue::DEFAULT.stats();
+        let weights = || Nnue::DEFAULT.ft.weights.iter().flatten().map(|&w| w as i32);
+        assert_eq!(stats.ft_weights.min, weights().fold(i32::MAX, i32::min));
+        assert_eq!(stats.ft_weights.max, weights().fold(i32::MIN, i32::max));
+        assert!(stats.ft_weights.min <= stats.ft_weights.max);
+        assert!((stats.ft_weights.min as f32..=stats.ft_weights.max as f32)
+            .contains(&stats.ft_weights.mean));
+    }
+
+    #[test]
+    fn display_prints_both_perspectives() {
+        let board = Board::default();
+        let state = Nnue::DEFAULT.state_from_board(&board);
+        let text = state.to_string();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), Color::NUM);
+        assert!(lines[0].starts_with("White:"));
+        assert!(lines[1].starts_with("Black:"));
+        for line in lines {
+            assert_eq!(line.split_whitespace().count(), FT_OUT + 1);
+        }
+    }
+}
@@ -125,6 +125,10 @@ impl CacheTable {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub fn clear(&mut self) {
         self.table.fill(None);
         self.len = 0;
@@ -6,6 +6,7 @@ use cozy_chess::*;
 use crate::eval::Eval;
 use crate::nnue::Nnue;
 
+#[allow(clippy::module_inception)]
 mod search;
 mod window;
 mod cache;
@@ -21,6 +22,7 @@ pub use params::*;
 use window::Window;
 pub use cache::{CacheTable, TableEntry, TableKeyValueEntry};
 use position::Position;
+pub(crate) use moves::static_exchange_evaluation;
 
 pub trait SearchHandler {
     fn stop_search(&self) -> bool;
@@ -51,13 +53,17 @@ pub struct SearchResult {
 
 #[derive(Debug, Clone)]
 pub struct EngineOptions {
-    pub max_depth: NonZeroU8
+    pub max_depth: NonZeroU8,
+    ///Centipawns added to the static eval for the side to move at the root,
+    ///and subtracted for the opponent. Positive values avoid draws.
+    pub contempt: i16
 }
 
 impl Default for EngineOptions {
     fn default() -> Self {
         Self {
-            max_depth: 64.try_into().unwrap()
+            max_depth: 64.try_into().unwrap(),
+            contempt: 0
         }
     }
 }
@@ -90,7 +96,8 @@ impl<H: SearchHandler> Engine<H> {
                 handler,
                 history,
                 cache_table,
-                search_params
+                search_params,
+                contempt: options.contempt
             },
             options
         }
@@ -8,7 +8,7 @@ use super::search::{KillerEntry, Searcher, KILLER_ENTRIES};
 mod see;
 mod partition;
 
-use see::*;
+pub(crate) use see::static_exchange_evaluation;
 use partition::*;
 
 // CITE: Move ordering.
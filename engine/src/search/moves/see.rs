@@ -16,6 +16,8 @@ fn piece_value(piece: Piece) -> Eval {
 
 // CITE: Static exchange evaluation.
 // https://www.chessprogramming.org/Static_Exchange_Evaluation
+///`capture` may also be a move to an empty square, such as a quiet promotion.
+///En passant is not handled, since the captured pawn isn't on the target square.
 pub fn static_exchange_evaluation(board: &Board, capture: Move) -> Eval {
     fn get_both_pawn_attacks(sq: Square) -> BitBoard {
         get_pawn_attacks(sq, Color::White) | get_pawn_attacks(sq, Color::Black)
@@ -29,9 +31,10 @@ pub fn static_exchange_evaluation(board: &Board, capture: Move) -> Eval {
 
     let sq = capture.to;
     let mut attacker_sq = capture.from;
-    let mut victim = board.piece_on(sq).unwrap();
+    let mut victim = board.piece_on(sq);
     let mut attacker = board.piece_on(attacker_sq).unwrap();
     let mut color = board.side_to_move();
+    let promotion_rank = matches!(sq.rank(), Rank::First | Rank::Eighth);
     let mut blockers = board.occupied();
     let mut attackers =
         get_king_moves(sq)                   & pieces!(King)           |
@@ -44,19 +47,34 @@ pub fn static_exchange_evaluation(board: &Board, capture: Move) -> Eval {
     let mut captures = ArrayVec::<Eval, 32>::new();
     'exchange: loop {
         //"Capture" victim
-        captures.push(piece_value(victim));
+        let mut gain = victim.map_or(Eval::ZERO, piece_value);
+        let moved = attacker;
+        if attacker == Piece::Pawn && promotion_rank {
+            //The pawn promotes as it captures. Recapturing pawns are assumed to promote to a queen.
+            let promotion = if captures.is_empty() {
+                capture.promotion.unwrap_or(Piece::Queen)
+            } else {
+                Piece::Queen
+            };
+            gain += piece_value(promotion) - piece_value(Piece::Pawn);
+            attacker = promotion;
+        }
+        captures.push(gain);
 
         //"Move" attacker to target square
+        //The first attacker isn't in the attackers if it moves without capturing.
         let attacker_bitboard = attacker_sq.bitboard();
         blockers ^= attacker_bitboard;
-        attackers ^= attacker_bitboard;
+        attackers &= !attacker_bitboard;
 
-        //Add new exposed sliding pieces
-        if matches!(attacker, Piece::Rook | Piece::Queen) {
-            attackers |= get_rook_moves(sq, blockers) & blockers & pieces!(Rook | Queen);
-        }
-        if matches!(attacker, Piece::Pawn | Piece::Bishop | Piece::Queen) {
-            attackers |= get_bishop_moves(sq, blockers) & blockers & pieces!(Bishop | Queen);
+        //Add new exposed sliding pieces behind the square the attacker left.
+        //This depends on the line it moved along, not on what it promoted to.
+        if moved != Piece::Knight {
+            if attacker_sq.file() == sq.file() || attacker_sq.rank() == sq.rank() {
+                attackers |= get_rook_moves(sq, blockers) & blockers & pieces!(Rook | Queen);
+            } else {
+                attackers |= get_bishop_moves(sq, blockers) & blockers & pieces!(Bishop | Queen);
+            }
         }
 
         //Swap sides
@@ -68,14 +86,14 @@ pub fn static_exchange_evaluation(board: &Board, capture: Move) -> Eval {
                 board.pieces(new_attacker) &
                 board.colors(color);
             if let Some(sq) = attackers.next() {
-                if victim == Piece::King {
+                if victim == Some(Piece::King) {
                     //Oops! Our last capture with our king was illegal since this piece is defended.
                     captures.pop();
                     break;
                 }
 
                 //New attacker, the old attacker is now the victim
-                victim = attacker;
+                victim = Some(attacker);
                 attacker = new_attacker;
                 attacker_sq = sq;
                 continue 'exchange;
@@ -97,3 +115,36 @@ pub fn static_exchange_evaluation(board: &Board, capture: Move) -> Eval {
         return captures.pop().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn see(fen: &str, mv: &str) -> Eval {
+        static_exchange_evaluation(&fen.parse().unwrap(), mv.parse().unwrap())
+    }
+
+    #[test]
+    fn underpromotion_capture_reveals_bishop() {
+        // bxc8=N, Nxc8, Bxc8
+        let fen = "2r4k/1P2n3/B7/8/8/8/8/7K w - - 0 1";
+        assert_eq!(see(fen, "b7c8n"), Eval::cp(500 + 320 - 100));
+        assert_eq!(see(fen, "b7c8q"), Eval::cp(500 + 320 - 100));
+    }
+
+    #[test]
+    fn promotion_push_reveals_rook() {
+        // c8=N, Nxc8, Rxc8
+        let fen = "7k/2P5/3n4/8/8/8/8/2R4K w - - 0 1";
+        assert_eq!(see(fen, "c7c8n"), Eval::cp(320 - 100));
+        assert_eq!(see(fen, "c7c8b"), Eval::cp(330 - 100 - (330 - 320)));
+    }
+
+    #[test]
+    fn defended_capture_loses_material() {
+        // Rxd5, cxd5
+        let fen = "4k3/8/2p5/3p4/8/8/8/3RK3 w - - 0 1";
+        assert_eq!(see(fen, "d1d5"), Eval::cp(100 - 500));
+        assert_eq!(crate::eval::see(&fen.parse().unwrap(), "d1d5".parse().unwrap()), -400);
+    }
+}
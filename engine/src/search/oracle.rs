@@ -6,6 +6,10 @@ use crate::eval::*;
 // https://www.chessprogramming.org/Oracle
 // https://www.chessprogramming.org/Interior_Node_Recognizer
 pub fn oracle(board: &Board) -> Option<Eval> {
+    if is_insufficient_material(board) {
+        return Some(Eval::DRAW);
+    }
+
     let all_pieces = board.occupied();
     let white_pieces = board.colors(Color::White);
     let bishops = board.pieces(Piece::Bishop);
@@ -13,26 +17,7 @@ pub fn oracle(board: &Board) -> Option<Eval> {
     let kings = board.pieces(Piece::King);
 
     match all_pieces.popcnt() {
-        2 => Some(Eval::DRAW),
-        3 => {
-            //KBvK and KNvK is always a draw
-            if !(bishops | knights).is_empty() {
-                Some(Eval::DRAW)
-            } else {
-                None
-            }
-        }
         4 => {
-            const DARK_SQUARES: BitBoard = bitboard! {
-                . X . X . X . X
-                X . X . X . X .
-                . X . X . X . X
-                X . X . X . X .
-                . X . X . X . X
-                X . X . X . X .
-                . X . X . X . X
-                X . X . X . X .
-            };
             const CORNERS: BitBoard = bitboard! {
                 X . . . . . . X
                 . . . . . . . .
@@ -51,22 +36,15 @@ pub fn oracle(board: &Board) -> Option<Eval> {
             if knights.popcnt() == 2 && (kings & BitBoard::EDGES).is_empty() {
                 return Some(Eval::DRAW);
             }
-            if bishops.popcnt() == 2 {
-                if (bishops & DARK_SQUARES).popcnt() != 1 {
-                    //Both bishops are on the same color square
-                    return Some(Eval::DRAW);
-                }
-                if one_piece_each && (kings & CORNERS).is_empty() {
-                    //Opposite color bishops. Check the corners
-                    //since there's technically one checkmate.
-                    return Some(Eval::DRAW);
-                }
+            if bishops.popcnt() == 2 && one_piece_each && (kings & CORNERS).is_empty() {
+                //Opposite color bishops, since same color bishops are insufficient material.
+                //Check the corners since there's technically one checkmate.
+                return Some(Eval::DRAW);
             }
-            if knights.popcnt() == 1 && bishops.popcnt() == 1 {
-                if one_piece_each && (kings & CORNERS).is_empty() {
-                    //Check the corners since there's technically one checkmate.
-                    return Some(Eval::DRAW);
-                }
+            if knights.popcnt() == 1 && bishops.popcnt() == 1
+                && one_piece_each && (kings & CORNERS).is_empty() {
+                //Check the corners since there's technically one checkmate.
+                return Some(Eval::DRAW);
             }
             None
         }
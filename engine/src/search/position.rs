@@ -1,7 +1,6 @@
-use arrayvec::ArrayVec;
 use cozy_chess::*;
 
-use crate::eval::Eval;
+use crate::eval::*;
 use crate::nnue::*;
 
 #[derive(Clone)]
@@ -12,19 +11,9 @@ pub struct Position<'s> {
 
 impl<'s> Position<'s> {
     pub fn new(model: &'s Nnue, board: Board) -> Self {
-        let mut nnue_state = model.new_state();
-        for &color in &Color::ALL {
-            let colors = board.colors(color);
-            for &piece in &Piece::ALL {
-                let pieces = board.pieces(piece);
-                for square in pieces & colors {
-                    nnue_state.add(color, piece, square);
-                }
-            }
-        }
         Self {
-            board,
-            nnue_state
+            nnue_state: model.state_from_board(&board),
+            board
         }
     }
 
@@ -33,41 +22,12 @@ impl<'s> Position<'s> {
     }
 
     pub fn play_unchecked(&self, mv: Move) -> Self {
-        let mut updates = ArrayVec::<_, 3>::new();
-        let moved = self.board.piece_on(mv.from).unwrap();
-        updates.push((self.board.color_on(mv.from).unwrap(), moved));
-        if let Some(color) = self.board.color_on(mv.to) {
-            updates.push((color, self.board.piece_on(mv.to).unwrap()));
-        }
-        if let Some(piece) = mv.promotion {
-            updates.push((self.board.color_on(mv.from).unwrap(), piece));
-        }
-        if moved == Piece::Pawn {
-            let ep_square = self.board.en_passant().map(|ep| {
-                Square::new(ep, Rank::Third.relative_to(!self.board.side_to_move()))
-            });
-            if Some(mv.to) == ep_square {
-                updates.push((!self.board.side_to_move(), Piece::Pawn));
-            }
-        }
         let mut new = self.clone();
+        new.nnue_state.apply_move(&self.board, mv);
         new.board.play_unchecked(mv);
-        for &(color, piece) in &updates {
-            let old_pieces = self.board.colors(color) & self.board.pieces(piece);
-            let new_pieces = new.board.colors(color) & new.board.pieces(piece);
-            for square in old_pieces & !new_pieces {
-                new.nnue_state.sub(color, piece, square);
-            }
-            for square in new_pieces & !old_pieces {
-                new.nnue_state.add(color, piece, square);
-            }
-        }
-        // debug_assert_eq!(
-        //     new.nnue_state.accumulator(),
-        //     Position::new(new.nnue_state.model(), new.board.clone()).nnue_state.accumulator(),
-        //     "{}\n{}\n{:?}",
-        //     self.board, mv, updates
-        // );
+        // Rebuilding the accumulator at every node is far too slow to leave on in debug builds.
+        #[cfg(feature = "verify-nnue")]
+        assert!(new.nnue_state.verify(&new.board), "{}\n{}", self.board, mv);
         new
     }
 
@@ -79,6 +39,11 @@ impl<'s> Position<'s> {
     }
 
     pub fn evaluate(&self) -> Eval {
-        Eval::cp(self.nnue_state.evaluate(self.board.side_to_move()) as i16)
+        let eval = self.nnue_state.evaluate(self.board.side_to_move());
+        adjust_nnue_eval(&self.board, eval, &DrawScaling::STANDARD)
+    }
+
+    pub fn evaluate_with_contempt(&self, contempt: i16) -> Eval {
+        apply_contempt(&self.board, self.evaluate(), contempt)
     }
 }
@@ -28,7 +28,8 @@ pub struct SearchSharedState<H> {
     pub handler: H,
     pub history: Vec<u64>,
     pub cache_table: CacheTable,
-    pub search_params: SearchParams
+    pub search_params: SearchParams,
+    pub contempt: i16
 }
 
 pub const KILLER_ENTRIES: usize = 2;
@@ -131,7 +132,7 @@ impl<H: SearchHandler> Searcher<'_, H> {
                 return Err(());
             }
 
-            if node != Node::Root && self.repetitions(&pos.board()) > 0 {
+            if node != Node::Root && self.repetitions(pos.board()) > 0 {
                 return Ok(Eval::DRAW);
             }
             match pos.board().status() {
@@ -169,7 +170,7 @@ impl<H: SearchHandler> Searcher<'_, H> {
                         None
                     }
                 })
-                .unwrap_or_else(|| pos.evaluate());
+                .unwrap_or_else(|| self.static_eval(pos, ply_index));
 
             if !matches!(node, Node::Root | Node::Pv) {
                 // CITE: Reverse futility pruning.
@@ -309,7 +310,7 @@ impl<H: SearchHandler> Searcher<'_, H> {
                     // CITE: We additionally punish the history of quiet moves that don't produce cutoffs.
                     // Suggested by the Black Marlin author and additionally observed in MadChess.
                     for &(prev_mv, _) in moves.yielded() {
-                        if prev_mv != mv && move_is_quiet(prev_mv, &pos.board()) {
+                        if prev_mv != mv && move_is_quiet(prev_mv, pos.board()) {
                             self.data.history_table.update(pos.board(), prev_mv, depth, false);
                         }
                     }
@@ -376,7 +377,7 @@ impl<H: SearchHandler> Searcher<'_, H> {
                 }
             }
 
-            let mut best_eval = pos.evaluate();
+            let mut best_eval = self.static_eval(pos, ply_index);
             window.narrow_alpha(best_eval);
             if window.empty() {
                 return best_eval;
@@ -415,4 +416,66 @@ impl<H: SearchHandler> Searcher<'_, H> {
             .filter(|&&hash| hash == board.hash())
             .count()
     }
+
+    fn static_eval(&self, pos: &Position, ply_index: u8) -> Eval {
+        //Contempt is given from the perspective of the side to move at the root.
+        let contempt = if ply_index.is_multiple_of(2) {
+            self.shared.contempt
+        } else {
+            -self.shared.contempt
+        };
+        pos.evaluate_with_contempt(contempt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nnue::Nnue;
+    use crate::search::SearchResult;
+
+    struct NullHandler;
+
+    impl SearchHandler for NullHandler {
+        fn stop_search(&self) -> bool {
+            true
+        }
+
+        fn new_result(&mut self, _: SearchResult) {}
+    }
+
+    #[test]
+    fn static_eval_contempt() {
+        const CONTEMPT: i16 = 30;
+        let mut shared = SearchSharedState {
+            handler: NullHandler,
+            history: Vec::new(),
+            cache_table: CacheTable::new_with_size(1 << 16).unwrap(),
+            search_params: SearchParams::default(),
+            contempt: CONTEMPT
+        };
+        let mut data = SearchData::new(Vec::new());
+        let searcher = Searcher {
+            shared: &mut shared,
+            data: &mut data,
+            search_result: None,
+            stats: SearchStats::default()
+        };
+
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let pos = Position::new(&Nnue::DEFAULT, fen.parse().unwrap());
+        let eval = pos.evaluate();
+        // The root side to move moves on even plies.
+        for ply_index in [0, 2, 10] {
+            assert_eq!(searcher.static_eval(&pos, ply_index), eval + Eval::cp(CONTEMPT));
+        }
+        for ply_index in [1, 3, 11] {
+            assert_eq!(searcher.static_eval(&pos, ply_index), eval - Eval::cp(CONTEMPT));
+        }
+
+        let dead_draw = Position::new(&Nnue::DEFAULT, "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1".parse().unwrap());
+        for ply_index in 0..4 {
+            assert_eq!(searcher.static_eval(&dead_draw, ply_index), Eval::DRAW);
+        }
+    }
 }
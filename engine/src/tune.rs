@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+
+use cozy_chess::*;
+
+use crate::eval::{evaluate, sigmoid};
+
+///Games that add no new positions before sampling gives up.
+const MAX_STALE_GAMES: u32 = 100;
+
+// CITE: xorshift64* generator.
+// https://en.wikipedia.org/wiki/Xorshift#xorshift*
+///A small seedable generator so sampling is reproducible without pulling in a dependency.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        // The state must be nonzero. Only one pair of seeds shares a state.
+        Self(seed.wrapping_add(0x9E37_79B9_7F4A_7C15).max(1))
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub fen: String,
+    ///The result of the game the position was sampled from, from White's perspective.
+    ///`1.0` is a White win, `0.5` a draw and `0.0` a Black win.
+    pub result: f32
+}
+
+///Sample positions from random games from the start position.
+///See [`sample_positions_from`].
+pub fn sample_positions(seed: u64, count: usize, plies: usize) -> Vec<Sample> {
+    sample_positions_from(&Board::default(), seed, count, plies)
+}
+
+///Sample up to `count` distinct positions by playing random legal moves from `board`.
+///Positions are taken from the first `plies` plies of each game, skipping positions
+///in check or where the game is over and any position already sampled.
+///Each game is played out to the end to label its positions with the result.
+///The same seed always gives the same samples.
+///Fewer than `count` positions are returned if the games stop producing new positions.
+pub fn sample_positions_from(board: &Board, seed: u64, count: usize, plies: usize) -> Vec<Sample> {
+    let mut rng = Rng::new(seed);
+    let mut seen = HashSet::new();
+    let mut samples = Vec::new();
+    let mut stale_games = 0;
+    while samples.len() < count && stale_games < MAX_STALE_GAMES {
+        let mut game = board.clone();
+        let mut positions = Vec::new();
+        let mut ply = 0;
+        while game.status() == GameStatus::Ongoing {
+            let mut moves = Vec::new();
+            game.generate_moves(|piece_moves| {
+                moves.extend(piece_moves);
+                false
+            });
+            game.play_unchecked(moves[rng.below(moves.len())]);
+            ply += 1;
+            let quiet = game.checkers().is_empty() && game.status() == GameStatus::Ongoing;
+            if ply <= plies && quiet && seen.insert(game.hash()) {
+                positions.push(format!("{}", game));
+            }
+        }
+        let result = match game.status() {
+            GameStatus::Won if game.side_to_move() == Color::Black => 1.0,
+            GameStatus::Won => 0.0,
+            _ => 0.5
+        };
+        stale_games = if positions.is_empty() { stale_games + 1 } else { 0 };
+        let remaining = count - samples.len();
+        samples.extend(positions.into_iter().take(remaining).map(|fen| Sample {
+            fen,
+            result
+        }));
+    }
+    samples
+}
+
+// CITE: Texel's tuning method.
+// https://www.chessprogramming.org/Texel%27s_Tuning_Method
+///Find the `K` for which `1 / (1 + e^(-K * eval))` best predicts the sample results,
+///where `eval` is [`evaluate`] in centipawns from White's perspective.
+///This minimizes the mean squared error with a ternary search over `0..=0.1`,
+///which assumes the error only has one minimum in that range.
+///# Panics
+///Panics if a sample's FEN is invalid.
+pub fn fit_k(samples: &[Sample]) -> f64 {
+    const ITERATIONS: u32 = 100;
+
+    let data: Vec<_> = samples.iter().map(|sample| {
+        let board = sample.fen.parse::<Board>().expect("invalid sample FEN");
+        let eval = evaluate(&board).as_cp().unwrap() as f64;
+        let eval = if board.side_to_move() == Color::White { eval } else { -eval };
+        (eval, sample.result as f64)
+    }).collect();
+    let error = |k: f64| data.iter()
+        .map(|&(eval, result)| (sigmoid(k * eval) - result).powi(2))
+        .sum::<f64>();
+    let (mut low, mut high) = (0.0, 0.1);
+    for _ in 0..ITERATIONS {
+        let third = (high - low) / 3.0;
+        if error(low + third) < error(high - third) {
+            high -= third;
+        } else {
+            low += third;
+        }
+    }
+    (low + high) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampling_is_reproducible() {
+        let samples = sample_positions(14, 50, 40);
+        assert_eq!(samples.len(), 50);
+        assert_eq!(samples, sample_positions(14, 50, 40));
+        assert_ne!(samples, sample_positions(15, 50, 40));
+    }
+
+    #[test]
+    fn samples_are_quiet_and_distinct() {
+        let mut seen = HashSet::new();
+        for sample in sample_positions(16, 200, 80) {
+            let board: Board = sample.fen.parse().unwrap();
+            assert!(board.checkers().is_empty(), "{}", sample.fen);
+            assert_eq!(board.status(), GameStatus::Ongoing, "{}", sample.fen);
+            assert!(seen.insert(board.hash()), "{}", sample.fen);
+        }
+    }
+
+    #[test]
+    fn samples_are_labeled_with_the_result() {
+        // Black's only move is Kg8, then every White move is a discovered mate.
+        let board = "RN5k/P3p2p/4P2P/8/8/6p1/6Pp/7K b - - 0 1".parse().unwrap();
+        let samples = sample_positions_from(&board, 17, 10, 10);
+        assert_eq!(samples, [Sample {
+            fen: "RN4k1/P3p2p/4P2P/8/8/6p1/6Pp/7K w - - 1 2".to_owned(),
+            result: 1.0
+        }]);
+        // The same position with the colors swapped.
+        let board = "7k/6pP/6P1/8/8/4p2p/p3P2P/rn5K w - - 0 1".parse().unwrap();
+        let samples = sample_positions_from(&board, 17, 10, 10);
+        assert_eq!(samples, [Sample {
+            fen: "7k/6pP/6P1/8/8/4p2p/p3P2P/rn4K1 b - - 1 1".to_owned(),
+            result: 0.0
+        }]);
+    }
+
+    #[test]
+    fn fit_k_recovers_the_labeling_k() {
+        let samples: Vec<_> = sample_positions(18, 200, 60).into_iter().map(|sample| {
+            let board: Board = sample.fen.parse().unwrap();
+            let eval = evaluate(&board).as_cp().unwrap() as f64;
+            let eval = if board.side_to_move() == Color::White { eval } else { -eval };
+            Sample {
+                result: sigmoid(eval / 115.0) as f32,
+                ..sample
+            }
+        }).collect();
+        let k = fit_k(&samples);
+        assert!((k - 1.0 / 115.0).abs() < 1e-6, "{}", k);
+    }
+}
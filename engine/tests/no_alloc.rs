@@ -0,0 +1,55 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use cozy_chess::*;
+use tantabus::eval::evaluate;
+use tantabus::nnue::Nnue;
+
+///Counts the allocations made by the current thread, so the test harness doesn't interfere.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+// SAFETY: Every call is forwarded to the system allocator.
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn static_evaluation_does_not_allocate() {
+    let boards: Vec<Board> = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        "4k3/8/8/4P3/8/8/r7/R5K1 b - - 0 1",
+        "4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1"
+    ].iter().map(|fen| fen.parse().unwrap()).collect();
+    for board in &boards {
+        assert_eq!(allocations(|| {
+            std::hint::black_box(evaluate(board));
+        }), 0, "{}", board);
+        assert_eq!(allocations(|| {
+            let state = Nnue::DEFAULT.state_from_board(board);
+            std::hint::black_box(state.evaluate(board.side_to_move()));
+        }), 0, "{}", board);
+    }
+    // The counter does see allocations.
+    assert_eq!(allocations(|| drop(std::hint::black_box(vec![0u8; 16]))), 1);
+}
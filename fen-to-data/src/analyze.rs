@@ -1,12 +1,6 @@
 use cozy_chess::*;
 use tantabus::search::*;
 
-const SCALE: f32 = 115.0;
-
-fn sigmoid(n: f32) -> f32 {
-    1.0 / (1.0 + (-n).exp())
-}
-
 const CACHE: usize = 1_000_000;
 
 struct Handler {
@@ -67,6 +61,7 @@ impl Analyzer {
         handler.prev_result.unwrap()
     }
 
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_data(&mut self, board: Board) -> Option<(Board, f32)> {
         if board.status() != GameStatus::Ongoing {
             return None;
@@ -83,8 +78,7 @@ impl Analyzer {
         if !is_quiet {
             return None;
         }
-        let eval = analysis.eval.as_cp().unwrap() as f32;
-        let win_rate = sigmoid(eval / SCALE);
+        let win_rate = analysis.eval.win_probability() as f32;
         Some((board, win_rate))
     }
 }
@@ -1,5 +1,7 @@
 use std::time::{Duration, Instant};
 
+use cozy_chess::*;
+use tantabus::nnue::*;
 use tantabus::search::*;
 use tantabus::search::SearchResult;
 
@@ -74,3 +76,121 @@ pub fn bench() {
     let nps = (total_nodes as f32 / total_time.as_secs_f32()) as u64;
     println!("{} nodes {} nps", total_nodes, nps);
 }
+
+const NNUE_DEPTH: u8 = 4;
+
+///How one accumulator implementation is driven by [`bench_nnue`].
+trait Traversal {
+    fn play(&mut self, board: &Board, mv: Move);
+
+    fn unplay(&mut self);
+
+    fn evaluate(&self, side_to_move: Color) -> i32;
+}
+
+impl Traversal for NnueState<'_> {
+    fn play(&mut self, board: &Board, mv: Move) {
+        NnueState::play(self, board, mv);
+    }
+
+    fn unplay(&mut self) {
+        NnueState::unplay(self);
+    }
+
+    fn evaluate(&self, side_to_move: Color) -> i32 {
+        NnueState::evaluate(self, side_to_move)
+    }
+}
+
+impl Traversal for LazyNnueState<'_> {
+    fn play(&mut self, board: &Board, mv: Move) {
+        LazyNnueState::play(self, board, mv);
+    }
+
+    fn unplay(&mut self) {
+        LazyNnueState::unplay(self);
+    }
+
+    fn evaluate(&self, side_to_move: Color) -> i32 {
+        LazyNnueState::evaluate(self, side_to_move)
+    }
+}
+
+#[derive(Default)]
+struct PerftStats {
+    leaves: u64,
+    checksum: i64
+}
+
+///Play every move to `depth`, evaluating every `stride`th leaf.
+fn perft(state: &mut impl Traversal, board: &Board, depth: u8, stride: u64, stats: &mut PerftStats) {
+    if depth == 0 {
+        if stats.leaves.is_multiple_of(stride) {
+            stats.checksum += state.evaluate(board.side_to_move()) as i64;
+        }
+        stats.leaves += 1;
+        return;
+    }
+    board.generate_moves(|moves| {
+        for mv in moves {
+            let mut child = board.clone();
+            child.play_unchecked(mv);
+            state.play(board, mv);
+            perft(state, &child, depth - 1, stride, stats);
+            state.unplay();
+        }
+        false
+    });
+}
+
+fn time_perft<T: Traversal>(new_state: impl Fn(&Board) -> T, stride: u64) -> (Duration, PerftStats) {
+    let mut stats = PerftStats::default();
+    let start_time = Instant::now();
+    for position in POSITIONS {
+        let board = position.parse().unwrap();
+        perft(&mut new_state(&board), &board, NNUE_DEPTH, stride, &mut stats);
+    }
+    (start_time.elapsed(), stats)
+}
+
+///Compare eager [`NnueState`] updates against [`LazyNnueState`] on a perft-style traversal
+///of the bench positions, once evaluating every leaf and once evaluating every 8th leaf.
+pub fn bench_nnue() {
+    for stride in [1, 8] {
+        let (eager_time, eager) = time_perft(|board| Nnue::DEFAULT.state_from_board(board), stride);
+        let (lazy_time, lazy) = time_perft(
+            |board| LazyNnueState::new(Nnue::DEFAULT.state_from_board(board)),
+            stride
+        );
+        assert_eq!(eager.checksum, lazy.checksum, "lazy and eager evaluations differ");
+        let leaves_per_second = |time: Duration| (eager.leaves as f32 / time.as_secs_f32()) as u64;
+        println!(
+            "1/{} leaves evaluated: eager {} leaves/s, lazy {} leaves/s",
+            stride, leaves_per_second(eager_time), leaves_per_second(lazy_time)
+        );
+    }
+}
+
+const BATCH_SIZE: usize = 100_000;
+
+///Compare [`tantabus::nnue::Nnue::evaluate_batch`] against evaluating the same boards in a serial loop.
+pub fn bench_batch() {
+    let boards: Vec<Board> = tantabus::tune::sample_positions(0, BATCH_SIZE, 100).into_iter()
+        .map(|sample| sample.fen.parse().unwrap())
+        .collect();
+    let start_time = Instant::now();
+    let serial: Vec<_> = boards.iter()
+        .map(|board| Nnue::DEFAULT.state_from_board(board).evaluate(board.side_to_move()))
+        .collect();
+    let serial_time = start_time.elapsed();
+    let start_time = Instant::now();
+    let batch = Nnue::DEFAULT.evaluate_batch(&boards);
+    let batch_time = start_time.elapsed();
+    assert_eq!(serial, batch, "batch and serial evaluations differ");
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let boards_per_second = |time: Duration| (boards.len() as f32 / time.as_secs_f32()) as u64;
+    println!(
+        "{} boards: serial {} boards/s, batch on {} threads {} boards/s",
+        boards.len(), boards_per_second(serial_time), threads, boards_per_second(batch_time)
+    );
+}
@@ -160,9 +160,20 @@ fn parse_message(msg: &str) -> UciMessage {
 }
 
 fn main() {
-    if std::env::args().nth(1).as_deref() == Some("bench") {
-        bench::bench();
-        return;
+    match std::env::args().nth(1).as_deref() {
+        Some("bench") => {
+            bench::bench();
+            return;
+        }
+        Some("bench-nnue") => {
+            bench::bench_nnue();
+            return;
+        }
+        Some("bench-batch") => {
+            bench::bench_batch();
+            return;
+        }
+        _ => {}
     }
     
     let mut position: Option<(Board, Vec<Move>)> = None;
@@ -219,8 +230,7 @@ fn main() {
                     position = Some((board, converted_moves));
                 }
                 UciMessage::Go { time_control, search_control } => {
-                    let time_manager;
-                    time_manager = match time_control {
+                    let time_manager = match time_control {
                         Some(UciTimeControl::MoveTime(time)) => StandardTimeManager::Fixed(time.to_std().unwrap()),
                         Some(UciTimeControl::TimeLeft {
                             white_time,
@@ -253,11 +263,11 @@ fn main() {
                             options.options.engine_options.max_depth = depth.try_into().unwrap();
                         }
                         //TODO implement the rest
-                        if let Some(_) = search_control.nodes {
+                        if search_control.nodes.is_some() {
                             let warn = "WARNING: The nodes search control is currently unimplemented.";
                             send_message(UciMessage::info_string(warn.to_owned()));
                         }
-                        if let Some(_) = search_control.mate {
+                        if search_control.mate.is_some() {
                             let warn = "WARNING: The mate search control is currently unimplemented.";
                             send_message(UciMessage::info_string(warn.to_owned()));
                         }
@@ -332,8 +342,8 @@ fn main() {
                     send_message(UciMessage::Info(vec![
                         match result.eval.kind() {
                             EvalKind::Centipawn(cp) => UciInfoAttribute::from_centipawns(cp as i32),
-                            EvalKind::MateIn(m) => UciInfoAttribute::from_mate(((m + 1) / 2) as i8),
-                            EvalKind::MatedIn(m) => UciInfoAttribute::from_mate(-(((m + 1) / 2) as i8))
+                            EvalKind::MateIn(m) => UciInfoAttribute::from_mate(m.div_ceil(2) as i8),
+                            EvalKind::MatedIn(m) => UciInfoAttribute::from_mate(-(m.div_ceil(2) as i8))
                         },
                         UciInfoAttribute::Depth(result.depth),
                         UciInfoAttribute::SelDepth(result.seldepth),
@@ -71,6 +71,16 @@ impl UciOptionsHandler {
             } => |_, _| {
                 // Implementation of the "Laziest SMP" algorithm
             }
+            UciOptionConfig::Spin {
+                name: "Contempt".to_owned(),
+                default: Some(options.engine_options.contempt as i64),
+                min: Some(-1000),
+                max: Some(1000)
+            } => |options, value| {
+                options.engine_options.contempt = value
+                    .parse()
+                    .unwrap();
+            }
         }
         macro_rules! add_search_param_handlers {
             ($([$($field:tt)*])*) => {